@@ -1,11 +1,33 @@
-use std::sync::mpsc::Sender;
-use crate::agent::exchange::Exchange;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use futures::{Stream, StreamExt};
+use serde_json::Value;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::{debug, warn};
+
+use crate::agent::answer::AnswerSegment;
+use crate::agent::budget::{Budget, DroppedContext};
+use crate::agent::exchange::{CodeChunk, Exchange, SearchStep, Update};
+use crate::agent::prompts;
+use crate::agent::tools::{self, ToolFormat};
 use crate::application::Application;
+use crate::llm::{ChatMessage, FunctionCall};
+use crate::llm::model::Model;
+use crate::repository::literal::Literal;
+use crate::repository::payload::{CodePayload, PayloadType};
+use crate::repository::semantic_query::SemanticQuery;
+
+/// Number of results of a code or path search.
+const SEARCH_LIMIT: u64 = 10;
 
 pub struct Agent {
     pub app: Application,
+    /// The model of the request, taken once so that a reload doesn't change it while it runs
+    pub model: Arc<Model>,
     pub exchanges: Vec<Exchange>,
-    pub exchange_tx: Sender<Exchange>,
+    pub exchange_tx: UnboundedSender<Exchange>,
 
     pub thread_id: uuid::Uuid,
     pub query_id: uuid::Uuid,
@@ -38,3 +60,999 @@ pub enum Action {
         paths: Vec<usize>,
     },
 }
+
+impl Action {
+    /// Parse the tool call of the model, like `["code", "backend error types"]` or
+    /// `["proc", "how does X work", [3, 6]]`, as described by [prompts::tool_prompt].
+    ///
+    /// Models often wrap the call in a markdown code block or add a sentence around it, so the
+    /// first JSON array of the text is used.
+    pub fn from_llm(text: &str) -> Result<Action> {
+        let start = text.find('[').ok_or(anyhow!("no tool call in `{}`", text))?;
+        let mut stream = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>();
+        let call = match stream.next() {
+            Some(Ok(Value::Array(call))) => call,
+            _ => bail!("tool call is not a JSON array: `{}`", text),
+        };
+
+        let name = call.first().and_then(Value::as_str).unwrap_or_default();
+        let query = || -> Result<String> {
+            call.get(1)
+                .and_then(Value::as_str)
+                .map(|it| it.to_string())
+                .ok_or(anyhow!("`{}` needs search terms", name))
+        };
+        let aliases = |index: usize| -> Vec<usize> {
+            call.get(index)
+                .and_then(Value::as_array)
+                .map(|it| it.iter().filter_map(Value::as_u64).map(|it| it as usize).collect())
+                .unwrap_or_default()
+        };
+
+        match name {
+            "code" => Ok(Action::Code { query: query()? }),
+            "path" => Ok(Action::Path { query: query()? }),
+            "proc" => Ok(Action::Proc { query: query()?, paths: aliases(2) }),
+            "none" => Ok(Action::Answer { paths: aliases(1) }),
+            _ => bail!("unknown tool `{}`", name),
+        }
+    }
+
+    /// Map a native function call, as defined by [tools::function_schemas], to the action.
+    pub fn from_function_call(call: &FunctionCall) -> Result<Action> {
+        // OpenAI returns the arguments as a JSON string
+        let arguments = match &call.arguments {
+            Value::String(arguments) => serde_json::from_str(arguments)?,
+            arguments => arguments.clone(),
+        };
+        let query = || -> Result<String> {
+            arguments.get("query")
+                .and_then(Value::as_str)
+                .map(|it| it.to_string())
+                .ok_or(anyhow!("`{}` needs a query", call.name))
+        };
+        let aliases = || -> Vec<usize> {
+            arguments.get("paths")
+                .and_then(Value::as_array)
+                .map(|it| it.iter().filter_map(Value::as_u64).map(|it| it as usize).collect())
+                .unwrap_or_default()
+        };
+
+        match call.name.as_str() {
+            "code" => Ok(Action::Code { query: query()? }),
+            "path" => Ok(Action::Path { query: query()? }),
+            "proc" => Ok(Action::Proc { query: query()?, paths: aliases() }),
+            "none" => Ok(Action::Answer { paths: aliases() }),
+            _ => bail!("unknown tool `{}`", call.name),
+        }
+    }
+}
+
+impl Agent {
+    pub fn new(app: Application, exchanges: Vec<Exchange>, exchange_tx: UnboundedSender<Exchange>, thread_id: uuid::Uuid) -> Self {
+        let query_id = exchanges.last().map_or(uuid::Uuid::new_v4(), |it| it.id);
+        Agent {
+            model: app.model.current(),
+            app,
+            exchanges,
+            exchange_tx,
+            thread_id,
+            query_id,
+            complete: false,
+        }
+    }
+
+    fn last_exchange(&self) -> &Exchange {
+        self.exchanges.last().expect("the agent needs an exchange")
+    }
+
+    fn last_exchange_mut(&mut self) -> &mut Exchange {
+        self.exchanges.last_mut().expect("the agent needs an exchange")
+    }
+
+    /// Apply an update to the current exchange, and publish the new state of the exchange.
+    fn update(&mut self, update: Update) {
+        self.last_exchange_mut().apply_update(update);
+        // nobody may be listening, e.g. when the answer is returned at once
+        let _ = self.exchange_tx.send(self.last_exchange().clone());
+    }
+
+    /// Run the tool loop until the model chooses `none`, or until `agent_max_steps` tool calls
+    /// were made, then write the answer.
+    pub async fn run(&mut self) -> Result<()> {
+        let query = self.last_exchange().query().ok_or(anyhow!("the exchange has no query"))?;
+        let mut action = Action::Query(query);
+        let mut steps = 0;
+
+        loop {
+            // the query and the answer are not tool calls
+            if !matches!(action, Action::Query(_) | Action::Answer { .. }) {
+                if steps == self.app.config.agent_max_steps {
+                    break;
+                }
+                steps += 1;
+            }
+
+            match self.step(action).await? {
+                Some(next) => action = next,
+                None => return self.finish(),
+            }
+        }
+
+        warn!("agent reached the max steps, answering with the information found so far");
+        let paths = (0..self.last_exchange().paths.len()).collect::<Vec<_>>();
+        self.answer(&paths).await?;
+        self.finish()
+    }
+
+    /// Mark the query as answered, and save the thread so that it can be followed up.
+    fn finish(&mut self) -> Result<()> {
+        self.complete = true;
+        if let Err(err) = self.app.conversations.save(self.thread_id, &self.exchanges) {
+            // the answer is still returned
+            warn!(%err, thread_id = %self.thread_id, "failed to save the conversation");
+        }
+        Ok(())
+    }
+
+    /// Run the agent, and yield the exchange after each update, `exchange_rx` is the receiver of
+    /// the agent's `exchange_tx`. A failure of the agent is yielded last.
+    ///
+    /// The agent runs while the stream is polled, so dropping the stream, e.g. when the client
+    /// disconnects, cancels the agent.
+    pub fn run_stream(mut self, mut exchange_rx: UnboundedReceiver<Exchange>) -> impl Stream<Item = Result<Exchange>> {
+        async_stream::stream! {
+            let result = {
+                let run = self.run();
+                tokio::pin!(run);
+                loop {
+                    let next = tokio::select! {
+                        biased;
+                        Some(exchange) = exchange_rx.recv() => Ok(exchange),
+                        result = &mut run => Err(result),
+                    };
+                    match next {
+                        Ok(exchange) => yield Ok(exchange),
+                        Err(result) => break result,
+                    }
+                }
+            };
+
+            while let Ok(exchange) = exchange_rx.try_recv() {
+                yield Ok(exchange);
+            }
+            if let Err(err) = result {
+                yield Err(err);
+            }
+        }
+    }
+
+    /// Execute one action, and ask the model for the next one. Returns `None` once answered.
+    pub async fn step(&mut self, action: Action) -> Result<Option<Action>> {
+        debug!(?action, "executing agent action");
+
+        match &action {
+            Action::Query(_) => {}
+            Action::Answer { paths } => {
+                self.answer(paths).await?;
+                return Ok(None);
+            }
+            Action::Code { query } => self.code_search(query).await?,
+            Action::Path { query } => self.path_search(query)?,
+            Action::Proc { query, paths } => self.process_files(query, paths).await?,
+        }
+
+        match self.next_action().await? {
+            Ok(action) => Ok(Some(action)),
+            Err(err) => {
+                // the model may not follow the tool schema, answering is better than failing
+                warn!(%err, "invalid tool call, answering with the information found so far");
+                Ok(Some(Action::Answer { paths: vec![] }))
+            }
+        }
+    }
+
+    /// The previous exchanges of the thread, for follow-up questions.
+    fn previous_exchanges(&self) -> impl Iterator<Item = &Exchange> {
+        self.exchanges[..self.exchanges.len() - 1].iter()
+    }
+
+    /// The conversation with the model: the tool prompt, the previous questions of the thread
+    /// with their conclusions, the user query, and each executed tool call followed by its result.
+    ///
+    /// It is cut to fit in the context window, in this order: the responses of the oldest tool
+    /// calls are truncated, then the paths without code chunks, the other paths and the oldest
+    /// questions of the thread are left out. The left out context is returned too.
+    fn history(&self) -> (Vec<ChatMessage>, Vec<DroppedContext>) {
+        let exchange = self.last_exchange();
+        let query = exchange.query().unwrap_or_default();
+        let mut budget = Budget::for_prompt(&self.app.tokens, &self.model);
+        let mut dropped = vec![];
+
+        budget.force_message(&prompts::tool_prompt(&self.app.templates, &[]));
+        budget.force_message(&query);
+
+        // the most recent tool calls first
+        let calls: Vec<String> = exchange.search_steps.iter().map(|step| self.tool_call(step).to_string()).collect();
+        calls.iter().for_each(|call| budget.force_message(call));
+        let mut responses = vec![String::new(); calls.len()];
+        for (index, step) in exchange.search_steps.iter().enumerate().rev() {
+            let response = step.get_response();
+            if budget.spend_message(&response) {
+                responses[index] = response;
+            } else {
+                responses[index] = format!("{}\n[truncated]", budget.truncate(&response));
+                dropped.push(DroppedContext::StepResponse { step: index });
+            }
+        }
+
+        // the paths of the code chunks first, as they are cited by the answer
+        let mut aliases: Vec<usize> = (0..exchange.paths.len()).collect();
+        aliases.sort_by_key(|alias| !exchange.code_chunks.iter().any(|it| it.alias == *alias));
+        let mut paths = vec![];
+        for alias in aliases {
+            let path = &exchange.paths[alias];
+            if budget.spend(&prompts::path_line(alias, path)) {
+                paths.push((alias, path.as_str()));
+            } else {
+                dropped.push(DroppedContext::Path { alias, path: path.clone() });
+            }
+        }
+        paths.sort();
+
+        // the most recent questions of the thread first
+        let mut previous_messages = vec![];
+        for previous in self.previous_exchanges().collect::<Vec<_>>().into_iter().rev() {
+            if let (Some(previous_query), Some((_, conclusion))) = (previous.query(), previous.answer()) {
+                if budget.spend_messages(&[&previous_query, conclusion]) {
+                    previous_messages.push([ChatMessage::user(&previous_query), ChatMessage::assistant(conclusion)]);
+                } else {
+                    dropped.push(DroppedContext::History { query: previous_query });
+                }
+            }
+        }
+
+        let mut history = vec![ChatMessage::system(&prompts::tool_prompt(&self.app.templates, &paths))];
+        history.extend(previous_messages.into_iter().rev().flatten());
+        history.push(ChatMessage::user(&query));
+        for (call, response) in calls.iter().zip(responses) {
+            history.push(ChatMessage::assistant(call));
+            history.push(ChatMessage::user(&response));
+        }
+
+        (history, dropped)
+    }
+
+    /// The tool call of the step, as the model writes it in the text format.
+    fn tool_call(&self, step: &SearchStep) -> serde_json::Value {
+        let exchange = self.last_exchange();
+        match step {
+            SearchStep::Path { query, .. } => serde_json::json!(["path", query]),
+            SearchStep::Code { query, .. } => serde_json::json!(["code", query]),
+            SearchStep::Proc { query, paths, .. } => {
+                let aliases: Vec<usize> = paths.iter()
+                    .filter_map(|path| exchange.paths.iter().position(|it| it == path))
+                    .collect();
+                serde_json::json!(["proc", query, aliases])
+            }
+        }
+    }
+
+    /// Keep the context which did not fit in the prompts in the exchange.
+    fn report_dropped(&mut self, dropped: Vec<DroppedContext>) {
+        if dropped.is_empty() {
+            return;
+        }
+
+        warn!(?dropped, "context left out of the prompt to fit in the context window");
+        self.update(Update::DropContext(dropped));
+    }
+
+    /// Ask the model for the next tool call, in the configured tool format. The outer error is a
+    /// failure of the model, the inner one an invalid tool call.
+    async fn next_action(&mut self) -> Result<Result<Action>> {
+        let llm = self.model.client.clone().ok_or(anyhow!("llm is not configured"))?;
+        let (history, dropped) = self.history();
+        self.report_dropped(dropped);
+
+        let format = self.model.tool_format;
+        if format == ToolFormat::Text {
+            let reply = llm.chat(&history).await?;
+            return Ok(Action::from_llm(&reply));
+        }
+
+        let call = llm.call_function(&history, &tools::function_schemas(format)).await?;
+        Ok(Action::from_function_call(&call))
+    }
+
+    /// A query for the semantic search, restricted like the user query, e.g. to some repos.
+    fn semantic_query(&self, query: &str) -> SemanticQuery<'static> {
+        SemanticQuery {
+            target: Some(Literal::Plain(Cow::Owned(query.to_string()))),
+            query_types: [Literal::Plain(Cow::Owned(PayloadType::Code.to_string()))].into(),
+            ..self.last_exchange().query.clone()
+        }
+    }
+
+    async fn semantic_search(&self, query: &str) -> Result<Vec<CodePayload>> {
+        let semantic = self.app.semantic.as_ref().ok_or(anyhow!("semantic search is not configured"))?;
+        semantic.search(&self.semantic_query(query), SEARCH_LIMIT, 0, 0.0, true).await
+    }
+
+    /// The alias of a path, which is its index in the exchange's paths.
+    fn get_path_alias(&mut self, path: &str) -> usize {
+        let paths = &mut self.last_exchange_mut().paths;
+        match paths.iter().position(|it| it == path) {
+            Some(alias) => alias,
+            None => {
+                paths.push(path.to_string());
+                paths.len() - 1
+            }
+        }
+    }
+
+    async fn code_search(&mut self, query: &str) -> Result<()> {
+        self.update(Update::StartStep(SearchStep::Code {
+            query: query.to_string(),
+            response: String::new(),
+        }));
+
+        let results = self.semantic_search(query).await?;
+        let mut chunks = vec![];
+        for payload in results {
+            let snippet = if payload.origin_text.is_empty() { payload.display_text } else { payload.origin_text };
+            let chunk = CodeChunk {
+                alias: self.get_path_alias(&payload.relative_path),
+                path: payload.relative_path,
+                start_line: 1,
+                end_line: snippet.lines().count(),
+                snippet,
+            };
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
+        }
+
+        let response = chunks.iter()
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        self.last_exchange_mut().code_chunks.extend(chunks);
+
+        self.update(Update::ReplaceStep(SearchStep::Code {
+            query: query.to_string(),
+            response,
+        }));
+        Ok(())
+    }
+
+    /// Fuzzy search of the paths of the ingested code, in the repos of the query.
+    fn path_search(&mut self, query: &str) -> Result<()> {
+        self.update(Update::StartStep(SearchStep::Path {
+            query: query.to_string(),
+            response: String::new(),
+        }));
+
+        let repos: Vec<String> = self.last_exchange().query.repos().map(|it| it.to_string()).collect();
+        let matches = self.app.paths.search(query, &repos, SEARCH_LIMIT as usize);
+        let mut lines = vec![];
+        for found in matches {
+            let alias = self.get_path_alias(&found.path);
+            lines.push(format!("{}, {}", alias, found.path));
+        }
+
+        self.update(Update::ReplaceStep(SearchStep::Path {
+            query: query.to_string(),
+            response: lines.join("\n"),
+        }));
+        Ok(())
+    }
+
+    /// The code of the aliased paths: the chunks found by the previous code searches, or the
+    /// results of a search in each path for the paths found by a path search.
+    async fn chunks_of_paths(&mut self, query: &str, paths: &[String]) -> Result<Vec<CodeChunk>> {
+        let mut chunks = vec![];
+        for path in paths {
+            let known: Vec<CodeChunk> = self.last_exchange().code_chunks.iter()
+                .filter(|chunk| &chunk.path == path)
+                .cloned()
+                .collect();
+            if !known.is_empty() {
+                chunks.extend(known);
+                continue;
+            }
+
+            let alias = self.get_path_alias(path);
+            let semantic = self.app.semantic.as_ref().ok_or(anyhow!("semantic search is not configured"))?;
+            let mut path_query = self.semantic_query(query);
+            path_query.paths = [Literal::Plain(Cow::Owned(path.clone()))].into();
+            for payload in semantic.search(&path_query, SEARCH_LIMIT, 0, 0.0, true).await? {
+                let chunk = CodeChunk {
+                    path: path.clone(),
+                    alias,
+                    start_line: 1,
+                    end_line: payload.origin_text.lines().count(),
+                    snippet: payload.origin_text,
+                };
+                if !chunk.is_empty() {
+                    chunks.push(chunk);
+                }
+            }
+        }
+
+        Ok(chunks)
+    }
+
+    /// Ask the model for the line ranges of the aliased paths which are relevant to the query,
+    /// with [prompts::file_explanation], and keep only these lines of the code chunks.
+    async fn process_files(&mut self, query: &str, aliases: &[usize]) -> Result<()> {
+        let paths: Vec<String> = aliases.iter()
+            .filter_map(|alias| self.last_exchange().paths.get(*alias).cloned())
+            .collect();
+        self.update(Update::StartStep(SearchStep::Proc {
+            query: query.to_string(),
+            paths: paths.clone(),
+            response: String::new(),
+        }));
+
+        let chunks = self.chunks_of_paths(query, &paths).await?;
+        let llm = self.model.client.clone().ok_or(anyhow!("llm is not configured"))?;
+        let answers = futures::future::join_all(chunks.iter().map(|chunk| {
+            let prompt = prompts::file_explanation(&self.app.templates, query, &chunk.path, &number_lines(&chunk.snippet));
+            let llm = llm.clone();
+            async move { llm.chat(&[ChatMessage::user(&prompt)]).await }
+        })).await;
+
+        let mut focused = vec![];
+        for (chunk, answer) in chunks.iter().zip(answers) {
+            let line_count = chunk.snippet.lines().count();
+            let ranges = parse_line_ranges(&answer?, line_count);
+            if ranges.is_empty() {
+                continue;
+            }
+
+            let lines: Vec<&str> = chunk.snippet.lines().collect();
+            for (start, end) in ranges {
+                focused.push(CodeChunk {
+                    path: chunk.path.clone(),
+                    alias: chunk.alias,
+                    snippet: lines[start - 1..end].join("\n"),
+                    start_line: chunk.start_line + start - 1,
+                    end_line: chunk.start_line + end - 1,
+                });
+            }
+            // the relevant lines replace the whole chunk in the context of the answer
+            self.last_exchange_mut().code_chunks.retain(|it| it != chunk);
+        }
+
+        let response = focused.iter()
+            .map(|it| it.to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let code_chunks = &mut self.last_exchange_mut().code_chunks;
+        for chunk in focused {
+            if !code_chunks.contains(&chunk) {
+                code_chunks.push(chunk);
+            }
+        }
+
+        self.update(Update::ReplaceStep(SearchStep::Proc {
+            query: query.to_string(),
+            paths,
+            response,
+        }));
+        Ok(())
+    }
+
+    /// Write the final answer with the code chunks of the chosen paths, in the order the model
+    /// chose them, or of all paths in the order they were found if the model chose none.
+    async fn answer(&mut self, aliases: &[usize]) -> Result<()> {
+        let exchange = self.last_exchange();
+        let mut chunks: Vec<&CodeChunk> = exchange.code_chunks.iter()
+            .filter(|chunk| aliases.is_empty() || aliases.contains(&chunk.alias))
+            .collect();
+        chunks.sort_by_key(|chunk| aliases.iter().position(|it| *it == chunk.alias));
+
+        // the best code chunks, the first one which doesn't fit is truncated, then the most
+        // recent questions of the thread, while they fit in the context window
+        let query = exchange.query().unwrap_or_default();
+        let mut budget = Budget::for_prompt(&self.app.tokens, &self.model);
+        let mut dropped = vec![];
+        budget.force_message(&prompts::final_explanation_prompt(&self.app.templates, "", &query, ""));
+
+        let mut context = String::new();
+        if !chunks.is_empty() {
+            context.push_str("##### CODE CHUNKS #####\n\n");
+            budget.force(&context);
+            let mut truncated = false;
+            for chunk in chunks {
+                let text = format!("{}\n\n", chunk);
+                if budget.spend(&text) {
+                    context.push_str(&text);
+                    continue;
+                }
+
+                let mut start_line = chunk.start_line;
+                let header = format!("{}: {}\n", chunk.alias, chunk.path);
+                if !truncated && budget.spend(&format!("{}\n[truncated]\n\n", header)) {
+                    truncated = true;
+                    // whole lines, so that the left out lines can be reported
+                    let snippet = budget.truncate(&chunk.snippet);
+                    let snippet = &snippet[..snippet.rfind('\n').unwrap_or(0)];
+                    if !snippet.is_empty() {
+                        context.push_str(&format!("{}{}\n[truncated]\n\n", header, snippet));
+                        start_line += snippet.lines().count();
+                    }
+                }
+                dropped.push(DroppedContext::CodeChunk {
+                    alias: chunk.alias,
+                    path: chunk.path.clone(),
+                    start_line,
+                    end_line: chunk.end_line,
+                });
+            }
+        }
+
+        let mut query_history = vec![];
+        for previous in self.previous_exchanges().collect::<Vec<_>>().into_iter().rev() {
+            let (Some(previous_query), Some((_, conclusion))) = (previous.query(), previous.answer()) else {
+                continue;
+            };
+            let text = format!("Q: {}\nA: {}\n\n", previous_query, conclusion);
+            if budget.spend(&text) {
+                query_history.push(text);
+            } else {
+                dropped.push(DroppedContext::History { query: previous_query });
+            }
+        }
+        let query_history: String = query_history.into_iter().rev().collect();
+
+        self.report_dropped(dropped);
+        let prompt = prompts::final_explanation_prompt(&self.app.templates, &context, &query, query_history.trim_end());
+        let llm = self.model.client.clone().ok_or(anyhow!("llm is not configured"))?;
+        let mut deltas = llm.chat_stream(&[ChatMessage::system(&prompt)]).await?;
+
+        // the exchange parses the partial answer, so it can be shown while it is written
+        let mut answer = String::new();
+        while let Some(delta) = deltas.next().await {
+            answer.push_str(&delta?);
+            self.update(Update::Article(answer.clone()));
+        }
+
+        let conclusion = self.last_exchange().answer_segments.iter()
+            .find_map(AnswerSegment::conclusion)
+            .map(|it| it.to_string())
+            .unwrap_or(answer);
+        self.update(Update::Conclude(conclusion));
+        Ok(())
+    }
+}
+
+/// Number the lines from 1, like `1 fn main() {`, as expected by [prompts::file_explanation].
+fn number_lines(code: &str) -> String {
+    code.lines()
+        .enumerate()
+        .map(|(index, line)| format!("{} {}", index + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `[[START, END], ...]` line ranges of the answer, which are inside the `line_count` numbered
+/// lines. A range which ends after the last line is cut, other invalid ranges are skipped.
+fn parse_line_ranges(answer: &str, line_count: usize) -> Vec<(usize, usize)> {
+    let Some(start) = answer.find('[') else {
+        return vec![];
+    };
+    let ranges = serde_json::Deserializer::from_str(&answer[start..])
+        .into_iter::<Vec<Vec<i64>>>()
+        .next()
+        .and_then(|it| it.ok())
+        .unwrap_or_default();
+
+    let mut valid: Vec<(usize, usize)> = vec![];
+    for range in ranges {
+        let [start, end] = range[..] else {
+            continue;
+        };
+        if start < 1 || start > end || start as usize > line_count {
+            continue;
+        }
+
+        let range = (start as usize, (end as usize).min(line_count));
+        if !valid.contains(&range) {
+            valid.push(range);
+        }
+    }
+
+    valid
+}
+
+impl Drop for Agent {
+    fn drop(&mut self) {
+        if !self.complete {
+            warn!(thread_id = %self.thread_id, query_id = %self.query_id, "agent stopped before answering the query");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::sync::Arc;
+
+    use futures::StreamExt;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    use serde_json::json;
+
+    use crate::agent::agent::{Action, Agent, number_lines, parse_line_ranges};
+    use crate::agent::answer::AnswerSegment;
+    use crate::agent::budget::DroppedContext;
+    use crate::agent::exchange::{CodeChunk, Exchange, SearchStep, Update};
+    use crate::agent::prompts;
+    use crate::agent::tools::ToolFormat;
+    use crate::application::Application;
+    use crate::configuration::Configuration;
+    use crate::llm::FunctionCall;
+    use crate::llm::model::Model;
+    use crate::llm::scripted_llm::ScriptedLlm;
+    use crate::llm::tokens::TokenCounter;
+    use crate::repository::literal::Literal;
+    use crate::repository::semantic_query::SemanticQuery;
+
+    /// An agent without semantic search, so only the tools which need no search can be used.
+    fn new_agent(llm: &Arc<ScriptedLlm>, max_steps: usize) -> (Agent, UnboundedReceiver<Exchange>) {
+        new_agent_with(llm, max_steps, ToolFormat::Text)
+    }
+
+    fn new_agent_with(llm: &Arc<ScriptedLlm>, max_steps: usize, tool_format: ToolFormat) -> (Agent, UnboundedReceiver<Exchange>) {
+        let mut config = Configuration::default();
+        config.agent_max_steps = max_steps;
+        config.llm_tool_format = tool_format;
+        let model = Model::new(&config, Some(llm.clone()));
+        let app = Application::for_tests(config, model);
+
+        let query = SemanticQuery {
+            target: Some(Literal::Plain(Cow::Borrowed("where are payments created"))),
+            ..Default::default()
+        };
+        let exchange = Exchange::new(uuid::Uuid::new_v4(), query);
+        let (exchange_tx, exchange_rx) = tokio::sync::mpsc::unbounded_channel();
+        (Agent::new(app, vec![exchange], exchange_tx, uuid::Uuid::new_v4()), exchange_rx)
+    }
+
+    #[test]
+    fn should_parse_tool_calls() {
+        assert!(matches!(Action::from_llm(r#"["code", "backend error types"]"#).unwrap(),
+            Action::Code { query } if query == "backend error types"));
+        assert!(matches!(Action::from_llm(r#"["path", "server/src"]"#).unwrap(), Action::Path { .. }));
+        assert!(matches!(Action::from_llm(r#"["proc", "how does X work", [3, 6]]"#).unwrap(),
+            Action::Proc { paths, .. } if paths == vec![3, 6]));
+        assert!(matches!(Action::from_llm(r#"["none", []]"#).unwrap(),
+            Action::Answer { paths } if paths.is_empty()));
+    }
+
+    #[test]
+    fn should_parse_tool_call_around_text() {
+        let text = "I will search the code first.\n```json\n[\"code\", \"payment\"]\n```";
+        assert!(matches!(Action::from_llm(text).unwrap(), Action::Code { query } if query == "payment"));
+    }
+
+    #[test]
+    fn should_parse_function_calls() {
+        let call = |name: &str, arguments: serde_json::Value| FunctionCall { name: name.to_string(), arguments };
+
+        assert!(matches!(Action::from_function_call(&call("code", json!({ "query": "payment" }))).unwrap(),
+            Action::Code { query } if query == "payment"));
+        assert!(matches!(Action::from_function_call(&call("proc", json!(r#"{"query": "how", "paths": [1, 2]}"#))).unwrap(),
+            Action::Proc { paths, .. } if paths == vec![1, 2]));
+        assert!(matches!(Action::from_function_call(&call("none", json!({}))).unwrap(),
+            Action::Answer { paths } if paths.is_empty()));
+        assert!(Action::from_function_call(&call("path", json!({ "q": "src" }))).is_err());
+        assert!(Action::from_function_call(&call("search", json!({ "query": "src" }))).is_err());
+    }
+
+    #[test]
+    fn should_reject_invalid_tool_calls() {
+        assert!(Action::from_llm("The answer is 42").is_err());
+        assert!(Action::from_llm(r#"["search", "payment"]"#).is_err());
+        assert!(Action::from_llm(r#"["code"]"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_answer_when_model_chooses_none() {
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["none", []]"#,
+            r#"[["con", "Payments are created in PaymentService"]]"#,
+        ]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        agent.run().await.unwrap();
+
+        assert!(agent.complete);
+        let exchange = agent.exchanges.last().unwrap();
+        assert_eq!(exchange.answer().unwrap().1, "Payments are created in PaymentService");
+        assert_eq!(exchange.answer_segments, vec![AnswerSegment::Conclusion {
+            summary: "Payments are created in PaymentService".to_string(),
+        }]);
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0][0].role, "system");
+        assert_eq!(requests[0][1].content, "where are payments created");
+        assert!(requests[1][0].content.contains("where are payments created"));
+    }
+
+    #[tokio::test]
+    async fn should_send_tool_results_back_to_model() {
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["proc", "find the entry", []]"#,
+            r#"["none", []]"#,
+            "PaymentService creates them",
+        ]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        agent.run().await.unwrap();
+
+        let exchange = agent.exchanges.last().unwrap();
+        assert!(matches!(&exchange.search_steps[..], [SearchStep::Proc { query, .. }] if query == "find the entry"));
+        // the conclusion falls back to the whole answer
+        assert_eq!(exchange.answer().unwrap().1, "PaymentService creates them");
+
+        let second = &llm.requests()[1];
+        assert_eq!(second.len(), 4);
+        assert_eq!(second[2].role, "assistant");
+        assert_eq!(second[2].content, r#"["proc","find the entry",[]]"#);
+    }
+
+    #[tokio::test]
+    async fn should_answer_on_invalid_tool_call_or_exhausted_steps() {
+        let llm = Arc::new(ScriptedLlm::new(vec!["I don't know which tool to use", "an answer"]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        agent.run().await.unwrap();
+        assert_eq!(agent.exchanges.last().unwrap().answer().unwrap().0, "an answer");
+
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["proc", "first", []]"#,
+            r#"["proc", "second", []]"#,
+            r#"["proc", "third", []]"#,
+            "an answer",
+        ]));
+        let (mut agent, _) = new_agent(&llm, 2);
+        agent.run().await.unwrap();
+        assert!(agent.complete);
+        assert_eq!(agent.exchanges.last().unwrap().search_steps.len(), 2);
+        assert_eq!(llm.requests().len(), 4);
+        assert_eq!(agent.exchanges.last().unwrap().answer().unwrap().0, "an answer");
+
+        // the answer chosen after the last tool call is not a tool call
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["proc", "first", []]"#,
+            r#"["none", []]"#,
+            "an answer",
+        ]));
+        let (mut agent, _) = new_agent(&llm, 1);
+        agent.run().await.unwrap();
+        assert_eq!(agent.exchanges.last().unwrap().search_steps.len(), 1);
+        assert_eq!(llm.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_fail_when_search_is_not_configured() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"["code", "payment"]"#]));
+        let (mut agent, _) = new_agent(&llm, 10);
+
+        assert!(agent.run().await.is_err());
+        assert!(!agent.complete);
+    }
+
+    #[tokio::test]
+    async fn should_stream_exchange_after_each_update() {
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["proc", "find the entry", []]"#,
+            r#"["none", []]"#,
+            "PaymentService creates them",
+        ]));
+        let (agent, exchange_rx) = new_agent(&llm, 10);
+        let exchanges: Vec<Exchange> = agent.run_stream(exchange_rx)
+            .map(|it| it.unwrap())
+            .collect()
+            .await;
+
+        // the started and finished proc step, the article after each streamed word, and the conclusion
+        assert_eq!(exchanges.len(), 6);
+        assert_eq!(exchanges[0].search_steps.len(), 1);
+        assert!(exchanges[1].answer().is_none());
+        assert_eq!(exchanges[2].answer.as_deref(), Some("PaymentService "));
+        assert_eq!(exchanges[5].answer().unwrap().1, "PaymentService creates them");
+    }
+
+    #[tokio::test]
+    async fn should_stream_failure_last() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"["code", "payment"]"#]));
+        let (agent, exchange_rx) = new_agent(&llm, 10);
+        let items: Vec<_> = agent.run_stream(exchange_rx).collect().await;
+
+        // the code step is started before the search fails
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_ok());
+        assert!(items[1].is_err());
+    }
+
+    #[tokio::test]
+    async fn should_follow_up_with_previous_exchanges() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"["none", []]"#, "Refunds are created in RefundService"]));
+        let (mut agent, _) = new_agent(&llm, 10);
+
+        let mut previous = agent.exchanges[0].clone();
+        previous.apply_update(Update::Article("Payments are created in PaymentService".to_string()));
+        previous.apply_update(Update::Conclude("PaymentService".to_string()));
+        let query = SemanticQuery {
+            target: Some(Literal::Plain(Cow::Borrowed("and refunds?"))),
+            ..Default::default()
+        };
+        agent.exchanges = vec![previous, Exchange::new(uuid::Uuid::new_v4(), query)];
+        agent.run().await.unwrap();
+
+        let requests = llm.requests();
+        let tool_history: Vec<&str> = requests[0].iter().skip(1).map(|it| it.content.as_str()).collect();
+        assert_eq!(tool_history, vec!["where are payments created", "PaymentService", "and refunds?"]);
+        assert!(requests[1][0].content.contains("Q: where are payments created\nA: PaymentService"));
+
+        let conversation = agent.app.conversations.get(agent.thread_id).unwrap();
+        assert_eq!(conversation.title, "where are payments created");
+        assert_eq!(conversation.exchanges.len(), 2);
+    }
+
+    #[test]
+    fn should_number_lines_and_validate_ranges() {
+        assert_eq!(number_lines("fn main() {\n}"), "1 fn main() {\n2 }");
+
+        assert_eq!(parse_line_ranges("A: [[2,3],[5,9],[4,1],[0,2],[12,14],[2,3],[1]]", 6), vec![(2, 3), (5, 6)]);
+        assert_eq!(parse_line_ranges("[]", 6), vec![]);
+        assert_eq!(parse_line_ranges("none of the lines", 6), vec![]);
+    }
+
+    #[tokio::test]
+    async fn should_keep_relevant_lines_of_processed_files() {
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"["proc", "where is pay called", [0]]"#,
+            "[[2,3],[7,9]]",
+            r#"["none", [0]]"#,
+            r#"[["cite", 0, "main calls pay", 11, 12], ["con", "pay is called by main"]]"#,
+        ]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        let exchange = agent.exchanges.last_mut().unwrap();
+        exchange.paths.push("src/payment.rs".to_string());
+        exchange.code_chunks.push(CodeChunk {
+            path: "src/payment.rs".to_string(),
+            alias: 0,
+            snippet: "fn main() {\n    pay();\n}\n\nfn pay() {}".to_string(),
+            start_line: 10,
+            end_line: 14,
+        });
+        agent.run().await.unwrap();
+
+        let requests = llm.requests();
+        assert!(requests[1][0].content.contains("1 fn main() {\n2     pay();\n3 }"));
+        assert!(requests[1][0].content.ends_with("Q: where is pay called\nA: "));
+
+        let exchange = agent.exchanges.last().unwrap();
+        assert_eq!(exchange.code_chunks, vec![CodeChunk {
+            path: "src/payment.rs".to_string(),
+            alias: 0,
+            snippet: "    pay();\n}".to_string(),
+            start_line: 11,
+            end_line: 12,
+        }]);
+        assert!(matches!(&exchange.search_steps[0], SearchStep::Proc { response, .. } if response == "0: src/payment.rs\n    pay();\n}"));
+        assert!(matches!(&exchange.answer_segments[0], AnswerSegment::Cite { chunk: Some(_), .. }));
+    }
+
+    #[tokio::test]
+    async fn should_search_indexed_paths() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"["path", "paymentsvc"]"#, r#"["none", [0]]"#, "PaymentService"]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        agent.app.paths.add("payment", [
+            "src/main/java/cc/unitmesh/payment/PaymentService.java",
+            "src/main/java/cc/unitmesh/refund/RefundService.java",
+        ]);
+        agent.run().await.unwrap();
+
+        let exchange = agent.exchanges.last().unwrap();
+        assert_eq!(exchange.paths, vec!["src/main/java/cc/unitmesh/payment/PaymentService.java"]);
+        assert_eq!(exchange.search_steps[0].get_response(), "0, src/main/java/cc/unitmesh/payment/PaymentService.java");
+        assert!(llm.requests()[1][0].content.contains("0, src/main/java/cc/unitmesh/payment/PaymentService.java\n"));
+    }
+
+    #[tokio::test]
+    async fn should_call_tools_with_native_function_calling() {
+        let llm = Arc::new(ScriptedLlm::new(vec![
+            r#"{"name": "proc", "arguments": "{\"query\": \"find the entry\", \"paths\": []}"}"#,
+            r#"{"name": "none", "input": {"paths": []}}"#,
+            "PaymentService creates them",
+        ]));
+        let (mut agent, _) = new_agent_with(&llm, 10, ToolFormat::OpenAi);
+        agent.run().await.unwrap();
+
+        let exchange = agent.exchanges.last().unwrap();
+        assert!(matches!(&exchange.search_steps[..], [SearchStep::Proc { query, .. }] if query == "find the entry"));
+        assert_eq!(exchange.answer().unwrap().1, "PaymentService creates them");
+    }
+
+    fn chunk(alias: usize, path: &str, snippet: String) -> CodeChunk {
+        CodeChunk { path: path.to_string(), alias, snippet, start_line: 1, end_line: 1 }
+    }
+
+    #[tokio::test]
+    async fn should_truncate_then_leave_out_code_chunks_over_the_context_window() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"[["con", "in PaymentService"]]"#]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        let exchange = agent.exchanges.last_mut().unwrap();
+        exchange.paths = vec!["src/Payment.java".to_string(), "src/Huge.java".to_string(), "src/Other.java".to_string()];
+        let mut huge = chunk(1, "src/Huge.java", "class Huge {}\n".repeat(4000));
+        huge.end_line = 4000;
+        exchange.code_chunks = vec![
+            chunk(0, "src/Payment.java", "class PaymentService {}".to_string()),
+            huge,
+            chunk(2, "src/Other.java", "class Other {}".to_string()),
+        ];
+
+        agent.step(Action::Answer { paths: vec![] }).await.unwrap();
+
+        let prompt = &llm.requests()[0][0].content;
+        assert!(prompt.contains("class PaymentService {}"));
+        assert!(prompt.contains("class Huge {}\n[truncated]"));
+        assert!(!prompt.contains("class Other {}"));
+        let dropped = &agent.exchanges.last().unwrap().dropped_context;
+        assert!(matches!(
+            &dropped[..],
+            [DroppedContext::CodeChunk { alias: 1, start_line, end_line: 4000, .. }, DroppedContext::CodeChunk { alias: 2, start_line: 1, .. }]
+                if *start_line > 1 && *start_line < 4000
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_put_code_chunks_in_the_order_of_the_chosen_paths() {
+        let llm = Arc::new(ScriptedLlm::new(vec![r#"[["con", "in PaymentService"]]"#]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        let exchange = agent.exchanges.last_mut().unwrap();
+        exchange.paths = vec!["src/Order.java".to_string(), "src/Payment.java".to_string(), "src/Other.java".to_string()];
+        exchange.code_chunks = vec![
+            chunk(0, "src/Order.java", "class OrderService {}".to_string()),
+            chunk(1, "src/Payment.java", "class PaymentService {}".to_string()),
+            chunk(2, "src/Other.java", "class Other {}".to_string()),
+        ];
+
+        agent.step(Action::Answer { paths: vec![1, 0] }).await.unwrap();
+
+        let prompt = &llm.requests()[0][0].content;
+        let payment = prompt.find("class PaymentService {}").unwrap();
+        assert!(payment < prompt.find("class OrderService {}").unwrap());
+        assert!(!prompt.contains("class Other {}"));
+    }
+
+    #[test]
+    fn should_keep_paths_of_code_chunks_first() {
+        let llm = Arc::new(ScriptedLlm::new(vec![]));
+        let (mut agent, _) = new_agent(&llm, 10);
+        let exchange = agent.exchanges.last_mut().unwrap();
+        exchange.paths = vec!["src/a.rs".to_string(), "src/b.rs".to_string(), "src/c.rs".to_string()];
+        exchange.code_chunks = vec![chunk(2, "src/c.rs", "fn c() {}".to_string())];
+
+        // room for the prompt, the query and a single path
+        let tokens = TokenCounter::Estimate;
+        let mut model = (*agent.model).clone();
+        model.context_window = model.max_completion_tokens
+            + tokens.count(&prompts::tool_prompt(&agent.app.templates, &[])) + 4
+            + tokens.count("where are payments created") + 4
+            + tokens.count(&prompts::path_line(2, "src/c.rs"));
+        agent.model = Arc::new(model);
+
+        let (history, dropped) = agent.history();
+        assert!(history[0].content.contains("2, src/c.rs"));
+        assert!(!history[0].content.contains("src/a.rs"));
+        assert_eq!(dropped, vec![
+            DroppedContext::Path { alias: 0, path: "src/a.rs".to_string() },
+            DroppedContext::Path { alias: 1, path: "src/b.rs".to_string() },
+        ]);
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::exchange::CodeChunk;
+
+/// A segment of the final answer, as described by [crate::agent::prompts::final_explanation_prompt].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnswerSegment {
+    /// `["cite", PATH ALIAS, COMMENT, START LINE, END LINE]`, with the cited lines of the code
+    /// chunks found by the agent, if they contain them
+    Cite {
+        alias: usize,
+        path: Option<String>,
+        comment: String,
+        start_line: usize,
+        end_line: usize,
+        chunk: Option<CodeChunk>,
+    },
+    /// `["dir", PATH, COMMENT]`
+    #[serde(rename = "dir")]
+    Directory {
+        path: String,
+        comment: String,
+    },
+    /// `["new", LANGUAGE, CODE]`
+    #[serde(rename = "new")]
+    NewFile {
+        language: String,
+        code: String,
+    },
+    /// `["mod", PATH ALIAS, LANGUAGE, GIT DIFF]`
+    #[serde(rename = "mod")]
+    Modify {
+        alias: usize,
+        path: Option<String>,
+        language: String,
+        diff: String,
+    },
+    /// `["con", SUMMARY]`
+    #[serde(rename = "con")]
+    Conclusion {
+        summary: String,
+    },
+}
+
+impl AnswerSegment {
+    pub fn conclusion(&self) -> Option<&str> {
+        match self {
+            AnswerSegment::Conclusion { summary } => Some(summary),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the answer of the model into segments, the aliases are resolved with the paths and code
+/// chunks of the exchange.
+///
+/// The parser is tolerant, as the answer may be:
+///
+/// - streamed: the last, unfinished segment is closed, so that e.g. the conclusion can be shown
+///   while it is written, unfinished citations are skipped
+/// - surrounded by text or a markdown code block
+/// - written like `["con": "..."]`, as in the example of the prompt
+///
+/// Segments which can't be parsed are skipped.
+pub fn parse_answer(text: &str, paths: &[String], chunks: &[CodeChunk]) -> Vec<AnswerSegment> {
+    raw_segments(text).iter()
+        .filter_map(|segment| to_segment(segment, paths, chunks))
+        .collect()
+}
+
+/// The arrays inside the outer array of the answer.
+fn raw_segments(text: &str) -> Vec<Vec<Value>> {
+    let Some(start) = text.find('[') else {
+        return vec![];
+    };
+    let body = &text[start + 1..];
+
+    let mut segments = vec![];
+    let mut depth = 0;
+    let mut segment_start = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, char) in body.char_indices() {
+        if in_string {
+            match char {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match char {
+            '"' => in_string = true,
+            '[' => {
+                if depth == 0 {
+                    segment_start = index;
+                }
+                depth += 1;
+            }
+            // the end of the outer array
+            ']' if depth == 0 => return segments,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    segments.extend(parse_segment(&body[segment_start..=index]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // close the unfinished segment of a streamed answer
+    if depth > 0 {
+        let mut partial = body[segment_start..].to_string();
+        if in_string {
+            if escaped {
+                partial.pop();
+            }
+            partial.push('"');
+        } else {
+            // a number may be unfinished too
+            partial = partial
+                .trim_end_matches(|it: char| it == ',' || it == '-' || it.is_ascii_digit() || it.is_whitespace())
+                .to_string();
+        }
+        partial.push_str(&"]".repeat(depth));
+        segments.extend(parse_segment(&partial));
+    }
+
+    segments
+}
+
+fn parse_segment(segment: &str) -> Option<Vec<Value>> {
+    // `["con": "summary"]`
+    static TYPO: OnceLock<Regex> = OnceLock::new();
+    let typo = TYPO.get_or_init(|| Regex::new(r#"^\[\s*"(\w+)"\s*:"#).unwrap());
+    let segment = typo.replace(segment, r#"["$1","#);
+
+    serde_json::from_str(&segment).ok()
+}
+
+fn to_segment(segment: &[Value], paths: &[String], chunks: &[CodeChunk]) -> Option<AnswerSegment> {
+    let text = |index: usize| segment.get(index).and_then(Value::as_str).map(|it| it.to_string());
+    // models sometimes quote the numbers
+    let number = |index: usize| -> Option<usize> {
+        match segment.get(index)? {
+            Value::Number(number) => number.as_u64().map(|it| it as usize),
+            Value::String(number) => number.trim().parse().ok(),
+            _ => None,
+        }
+    };
+
+    match segment.first()?.as_str()? {
+        "cite" => {
+            let alias = number(1)?;
+            let (start_line, end_line) = (number(3)?, number(4)?);
+            Some(AnswerSegment::Cite {
+                alias,
+                path: paths.get(alias).cloned(),
+                comment: text(2)?,
+                start_line,
+                end_line,
+                chunk: cited_chunk(chunks, alias, start_line, end_line),
+            })
+        }
+        "dir" => Some(AnswerSegment::Directory { path: text(1)?, comment: text(2).unwrap_or_default() }),
+        "new" => Some(AnswerSegment::NewFile { language: text(1)?, code: text(2).unwrap_or_default() }),
+        "mod" => {
+            let alias = number(1)?;
+            Some(AnswerSegment::Modify {
+                alias,
+                path: paths.get(alias).cloned(),
+                language: text(2)?,
+                diff: text(3).unwrap_or_default(),
+            })
+        }
+        "con" => Some(AnswerSegment::Conclusion { summary: text(1)? }),
+        _ => None,
+    }
+}
+
+/// The cited lines, from the chunk of the alias which contains them.
+fn cited_chunk(chunks: &[CodeChunk], alias: usize, start_line: usize, end_line: usize) -> Option<CodeChunk> {
+    if start_line > end_line {
+        return None;
+    }
+
+    let chunk = chunks.iter()
+        .find(|it| it.alias == alias && it.start_line <= start_line && end_line <= it.end_line)?;
+    let snippet = chunk.snippet.lines()
+        .skip(start_line - chunk.start_line)
+        .take(end_line - start_line + 1)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(CodeChunk {
+        path: chunk.path.clone(),
+        alias,
+        snippet,
+        start_line,
+        end_line,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::answer::{AnswerSegment, parse_answer};
+    use crate::agent::exchange::CodeChunk;
+
+    fn chunks() -> Vec<CodeChunk> {
+        vec![CodeChunk {
+            path: "src/payment.rs".to_string(),
+            alias: 0,
+            snippet: "fn main() {\n    pay();\n}\n\nfn pay() {}".to_string(),
+            start_line: 10,
+            end_line: 14,
+        }]
+    }
+
+    #[test]
+    fn should_parse_segments_and_resolve_aliases() {
+        let answer = r#"```json
+[
+  ["cite", 0, "pay is called by main", 11, 12],
+  ["cite", 3, "unknown alias", 1, 2],
+  ["dir", "src/", "the sources"],
+  ["new", "rust", "fn refund() {}"],
+  ["mod", 0, "rust", "@@ -1 +1 @@\n-a\n+b"],
+  ["con", "Payments are made by pay"]
+]
+```"#;
+        let segments = parse_answer(answer, &["src/payment.rs".to_string()], &chunks());
+
+        assert_eq!(segments.len(), 6);
+        match &segments[0] {
+            AnswerSegment::Cite { path, chunk: Some(chunk), .. } => {
+                assert_eq!(path.as_deref(), Some("src/payment.rs"));
+                assert_eq!(chunk.snippet, "    pay();\n}");
+                assert_eq!((chunk.start_line, chunk.end_line), (11, 12));
+            }
+            segment => panic!("unexpected {:?}", segment),
+        }
+        assert!(matches!(&segments[1], AnswerSegment::Cite { path: None, chunk: None, .. }));
+        assert!(matches!(&segments[4], AnswerSegment::Modify { path: Some(_), .. }));
+        assert_eq!(segments[5].conclusion(), Some("Payments are made by pay"));
+    }
+
+    #[test]
+    fn should_parse_conclusion_typo_of_prompt_example() {
+        let segments = parse_answer(r#"[["con": "None of files contain MAX_FILE_LEN"]]"#, &[], &[]);
+        assert_eq!(segments[0].conclusion(), Some("None of files contain MAX_FILE_LEN"));
+    }
+
+    #[test]
+    fn should_parse_partial_streamed_answer() {
+        let answer = r#"[["cite", "0", "pay [is] \"called\"", 11, 12], ["con", "Payments are"#;
+        let segments = parse_answer(answer, &[], &chunks());
+        assert_eq!(segments.len(), 2);
+        assert!(matches!(&segments[0], AnswerSegment::Cite { comment, .. } if comment == "pay [is] \"called\""));
+        assert_eq!(segments[1].conclusion(), Some("Payments are"));
+
+        // an unfinished citation is skipped
+        assert!(parse_answer(r#"[["cite", 0, "pay is called", 11,"#, &[], &[]).is_empty());
+        assert!(parse_answer(r#"[["cite", 0, "pay is called", 11, 1"#, &[], &[]).is_empty());
+        assert!(parse_answer("no answer", &[], &[]).is_empty());
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+use crate::llm::model::Model;
+use crate::llm::tokens::TokenCounter;
+
+/// The tokens of the chat format around each message.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// A part of the context which did not fit in the prompt.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DroppedContext {
+    /// a path of the PATHS of the tool prompt
+    Path { alias: usize, path: String },
+    /// a code chunk of the final answer
+    CodeChunk { alias: usize, path: String, start_line: usize, end_line: usize },
+    /// a previous question of the thread
+    History { query: String },
+    /// the response of a tool call, which was truncated
+    StepResponse { step: usize },
+}
+
+/// The tokens left for a prompt, in the context window of the model, after its completion.
+pub struct Budget<'a> {
+    counter: &'a TokenCounter,
+    remaining: usize,
+}
+
+impl<'a> Budget<'a> {
+    pub fn new(counter: &'a TokenCounter, tokens: usize) -> Self {
+        Budget { counter, remaining: tokens }
+    }
+
+    pub fn for_prompt(counter: &'a TokenCounter, model: &Model) -> Self {
+        Budget::new(counter, model.context_window.saturating_sub(model.max_completion_tokens))
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Spend the tokens of a part of the prompt which must be kept, even over the budget.
+    pub fn force(&mut self, text: &str) {
+        self.remaining = self.remaining.saturating_sub(self.counter.count(text));
+    }
+
+    /// Spend the tokens of a message which must be kept.
+    pub fn force_message(&mut self, text: &str) {
+        self.force(text);
+        self.remaining = self.remaining.saturating_sub(MESSAGE_OVERHEAD);
+    }
+
+    /// Spend the tokens of the text if it fits, returns whether it does.
+    pub fn spend(&mut self, text: &str) -> bool {
+        self.spend_tokens(self.counter.count(text))
+    }
+
+    /// Spend the tokens of a message if it fits, returns whether it does.
+    pub fn spend_message(&mut self, text: &str) -> bool {
+        self.spend_tokens(self.counter.count(text) + MESSAGE_OVERHEAD)
+    }
+
+    /// Spend the tokens of the messages if they all fit, returns whether they do, so that a
+    /// question isn't kept without its answer.
+    pub fn spend_messages(&mut self, texts: &[&str]) -> bool {
+        let tokens = texts.iter().map(|it| self.counter.count(it) + MESSAGE_OVERHEAD).sum();
+        self.spend_tokens(tokens)
+    }
+
+    /// The beginning of the text which fits, the tokens of which are spent.
+    pub fn truncate<'t>(&mut self, text: &'t str) -> &'t str {
+        let truncated = self.counter.truncate(text, self.remaining);
+        self.force(truncated);
+        truncated
+    }
+
+    fn spend_tokens(&mut self, tokens: usize) -> bool {
+        if tokens > self.remaining {
+            return false;
+        }
+        self.remaining -= tokens;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::budget::Budget;
+    use crate::llm::tokens::TokenCounter;
+
+    #[test]
+    fn should_spend_what_fits() {
+        let counter = TokenCounter::Estimate;
+        let mut budget = Budget::new(&counter, 10);
+
+        assert!(budget.spend("12345678"));
+        assert!(!budget.spend(&"1234567890".repeat(4)));
+        assert!(!budget.spend_message("12345678901234567890"));
+        assert_eq!(budget.remaining(), 8);
+        assert!(!budget.spend_messages(&["", "1234"]));
+        assert_eq!(budget.remaining(), 8);
+
+        assert_eq!(budget.truncate(&"1234567890".repeat(4)), "12345678901234567890123456789012");
+        assert_eq!(budget.remaining(), 0);
+        budget.force("more");
+        assert_eq!(budget.remaining(), 0);
+    }
+}
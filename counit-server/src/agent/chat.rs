@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::agent::budget::Budget;
+use crate::agent::prompts;
+use crate::application::Application;
+use crate::llm::ChatMessage;
+use crate::llm::model::Model;
+use crate::repository::literal::Literal;
+use crate::repository::payload::{CodePayload, PayloadType};
+use crate::repository::semantic_query::SemanticQuery;
+
+/// The snippets retrieved for the question of a chat.
+const CONTEXT_LIMIT: u64 = 10;
+
+/// A snippet of the context of a chat, `index` is its number in the prompt, like `[1]`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub index: usize,
+    pub repo: String,
+    pub path: String,
+    pub lang: String,
+    pub payload_type: PayloadType,
+}
+
+/// What the retrieval added to a chat.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChatContext {
+    /// the question after the glossary, which was searched
+    pub query: String,
+    pub citations: Vec<Citation>,
+}
+
+/// A chat with the snippets of the codebase: the last question of the user is translated by the
+/// glossary, searched, and sent to the model with the snippets found in a system message. The
+/// prompt fits in the context window of the model which answers the request.
+pub async fn retrieval_chat(
+    app: &Application,
+    model: &Model,
+    messages: &[ChatMessage],
+    repos: &[String],
+    payload_type: PayloadType,
+) -> Result<(Vec<ChatMessage>, ChatContext)> {
+    let Some(semantic) = app.semantic.as_ref() else {
+        bail!("semantic search is disabled because `qdrant_url` is not provided");
+    };
+    let Some(question) = messages.iter().rposition(|it| it.role == "user") else {
+        bail!("the chat has no message of the user");
+    };
+
+    let query_text = app.transpiler.transpile(&messages[question].content);
+    let mut messages = messages.to_vec();
+    messages[question].content = query_text.clone();
+
+    let mut query = SemanticQuery::from_str(query_text.clone(), payload_type);
+    query.repos = repos.iter().map(|it| Literal::Plain(Cow::Owned(it.clone()))).collect();
+    let payloads = semantic.search(&query, CONTEXT_LIMIT, 0, 0.0, true).await?;
+
+    let (prompt, citations) = retrieval_prompt(app, model, &messages, &payloads);
+    Ok((prompt, ChatContext { query: query_text, citations }))
+}
+
+/// The messages of the chat with a system message of the snippets, in the context window: the
+/// leading system messages of the client and the last message are kept, then the snippets in
+/// the order of the search, then the previous messages, the most recent first.
+fn retrieval_prompt(app: &Application, model: &Model, messages: &[ChatMessage], payloads: &[CodePayload]) -> (Vec<ChatMessage>, Vec<Citation>) {
+    let system_count = messages.iter().take_while(|it| it.role == "system").count();
+    let (system, messages) = messages.split_at(system_count);
+    let Some((last, previous)) = messages.split_last() else {
+        return (system.to_vec(), vec![]);
+    };
+
+    let mut budget = Budget::for_prompt(&app.tokens, model);
+    system.iter().for_each(|it| budget.force_message(&it.content));
+    budget.force_message(&prompts::chat_context_prompt(&app.templates, ""));
+    budget.force_message(&last.content);
+
+    let mut context = String::new();
+    let mut citations = vec![];
+    for payload in payloads {
+        let index = citations.len() + 1;
+        let snippet = if payload.origin_text.is_empty() { &payload.display_text } else { &payload.origin_text };
+        let text = format!("[{}] {}/{}\n```{}\n{}\n```\n\n", index, payload.repo_name, payload.relative_path, payload.lang, snippet);
+        if !budget.spend(&text) {
+            continue;
+        }
+
+        context.push_str(&text);
+        citations.push(Citation {
+            index,
+            repo: payload.repo_name.clone(),
+            path: payload.relative_path.clone(),
+            lang: payload.lang.clone(),
+            payload_type: payload.payload_type.clone(),
+        });
+    }
+
+    let kept = previous.iter().rev()
+        .take_while(|message| budget.spend_message(&message.content))
+        .count();
+
+    let mut prompt = system.to_vec();
+    prompt.push(ChatMessage::system(&prompts::chat_context_prompt(&app.templates, context.trim_end())));
+    prompt.extend_from_slice(&previous[previous.len() - kept..]);
+    prompt.push(last.clone());
+    (prompt, citations)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::agent::chat::retrieval_prompt;
+    use crate::application::Application;
+    use crate::configuration::Configuration;
+    use crate::llm::ChatMessage;
+    use crate::llm::model::Model;
+    use crate::repository::payload::CodePayload;
+
+    fn payload(path: &str, text: &str) -> CodePayload {
+        CodePayload {
+            repo_name: "payment".to_string(),
+            relative_path: path.to_string(),
+            lang: "java".to_string(),
+            origin_text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_fit_snippets_and_history_in_context_window() {
+        let mut config = Configuration::default();
+        config.llm_context_window = 400;
+        config.llm_max_completion_tokens = 100;
+        let model = Model::new(&config, None);
+        let app = Application::for_tests(config, model);
+
+        let messages = vec![
+            ChatMessage::system(&"You are a helpful assistant. ".repeat(20)),
+            ChatMessage::user(&"an old question ".repeat(50)),
+            ChatMessage::assistant("an answer"),
+            ChatMessage::user("where are payments created?"),
+        ];
+        let payloads = vec![
+            payload("PaymentService.java", "class PaymentService {}"),
+            payload("Huge.java", &"1234567890".repeat(200)),
+            payload("PaymentController.java", "class PaymentController {}"),
+        ];
+
+        let (prompt, citations) = retrieval_prompt(&app, &app.model.current(), &messages, &payloads);
+
+        // the huge snippet doesn't fit, neither does the old question
+        let paths: Vec<(usize, &str)> = citations.iter().map(|it| (it.index, it.path.as_str())).collect();
+        assert_eq!(paths, vec![(1, "PaymentService.java"), (2, "PaymentController.java")]);
+        assert_eq!(prompt.len(), 4);
+        assert_eq!(prompt[0], messages[0]);
+        assert!(prompt[1].content.contains("[1] payment/PaymentService.java\n```java\nclass PaymentService {}\n```"));
+        assert!(!prompt[1].content.contains("Huge.java"));
+        assert_eq!(prompt[2], ChatMessage::assistant("an answer"));
+        assert_eq!(prompt[3], ChatMessage::user("where are payments created?"));
+    }
+}
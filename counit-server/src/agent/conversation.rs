@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::agent::exchange::Exchange;
+
+/// The exchanges of a thread, the follow-up questions of a thread are answered with the previous
+/// exchanges as history.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Conversation {
+    pub thread_id: uuid::Uuid,
+    /// The first query of the thread
+    pub title: String,
+    pub exchanges: Vec<Exchange>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A conversation without its exchanges, to list the threads.
+#[derive(Serialize, Debug, Clone)]
+pub struct ConversationSummary {
+    pub thread_id: uuid::Uuid,
+    pub title: String,
+    pub exchange_count: usize,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The conversations of the agent, each one is kept as `<thread_id>.json` in the directory, or
+/// only in memory if there is no directory.
+#[derive(Default)]
+pub struct ConversationStore {
+    dir: Option<PathBuf>,
+    conversations: RwLock<HashMap<uuid::Uuid, Conversation>>,
+}
+
+impl ConversationStore {
+    /// Load the conversations of the directory, files which can't be read are skipped.
+    pub fn load(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+        let mut conversations = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|it| it.to_str()) != Some("json") {
+                continue;
+            }
+
+            let conversation = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|it| Ok(serde_json::from_str::<Conversation>(&it)?));
+            match conversation {
+                Ok(conversation) => {
+                    conversations.insert(conversation.thread_id, conversation);
+                }
+                Err(err) => warn!(?path, %err, "skipping invalid conversation"),
+            }
+        }
+
+        Ok(ConversationStore {
+            dir: Some(dir.to_path_buf()),
+            conversations: RwLock::new(conversations),
+        })
+    }
+
+    /// Save the exchanges of the thread, replacing the previous ones.
+    pub fn save(&self, thread_id: uuid::Uuid, exchanges: &[Exchange]) -> Result<()> {
+        let conversation = Conversation {
+            thread_id,
+            title: exchanges.first().and_then(|it| it.query()).unwrap_or_default(),
+            exchanges: exchanges.to_vec(),
+            updated_at: Utc::now(),
+        };
+
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", thread_id));
+            std::fs::write(&path, serde_json::to_string(&conversation)?)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+
+        self.conversations.write().unwrap().insert(thread_id, conversation);
+        Ok(())
+    }
+
+    pub fn get(&self, thread_id: uuid::Uuid) -> Option<Conversation> {
+        self.conversations.read().unwrap().get(&thread_id).cloned()
+    }
+
+    /// The threads, the most recently updated first.
+    pub fn list(&self) -> Vec<ConversationSummary> {
+        let mut summaries: Vec<ConversationSummary> = self.conversations.read().unwrap()
+            .values()
+            .map(|it| ConversationSummary {
+                thread_id: it.thread_id,
+                title: it.title.clone(),
+                exchange_count: it.exchanges.len(),
+                updated_at: it.updated_at,
+            })
+            .collect();
+        summaries.sort_by_key(|it| std::cmp::Reverse(it.updated_at));
+        summaries
+    }
+
+    /// Delete the thread, returns whether it existed.
+    pub fn delete(&self, thread_id: uuid::Uuid) -> Result<bool> {
+        let removed = self.conversations.write().unwrap().remove(&thread_id).is_some();
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", thread_id));
+            if path.exists() {
+                std::fs::remove_file(&path).with_context(|| format!("failed to delete {}", path.display()))?;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::agent::conversation::ConversationStore;
+    use crate::agent::exchange::Exchange;
+    use crate::repository::literal::Literal;
+    use crate::repository::semantic_query::SemanticQuery;
+
+    fn exchange(query: &'static str) -> Exchange {
+        let query = SemanticQuery {
+            target: Some(Literal::Plain(Cow::Borrowed(query))),
+            ..Default::default()
+        };
+        Exchange::new(uuid::Uuid::new_v4(), query)
+    }
+
+    #[test]
+    fn should_persist_conversations_in_directory() {
+        let dir = std::env::temp_dir().join(format!("counit-conversations-{}", uuid::Uuid::new_v4()));
+        let thread_id = uuid::Uuid::new_v4();
+
+        let store = ConversationStore::load(&dir).unwrap();
+        store.save(thread_id, &[exchange("where are payments created"), exchange("and refunds?")]).unwrap();
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let reloaded = ConversationStore::load(&dir).unwrap();
+        let conversation = reloaded.get(thread_id).unwrap();
+        assert_eq!(conversation.title, "where are payments created");
+        assert_eq!(conversation.exchanges[1].query().as_deref(), Some("and refunds?"));
+        assert_eq!(reloaded.list().len(), 1);
+
+        assert!(reloaded.delete(thread_id).unwrap());
+        assert!(!reloaded.delete(thread_id).unwrap());
+        assert!(ConversationStore::load(&dir).unwrap().get(thread_id).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_list_recent_threads_first() {
+        let store = ConversationStore::default();
+        let first = uuid::Uuid::new_v4();
+        let second = uuid::Uuid::new_v4();
+        store.save(first, &[exchange("first")]).unwrap();
+        store.save(second, &[exchange("second")]).unwrap();
+        store.save(first, &[exchange("first"), exchange("follow-up")]).unwrap();
+
+        let titles: Vec<(String, usize)> = store.list().into_iter().map(|it| (it.title, it.exchange_count)).collect();
+        assert_eq!(titles, vec![("first".to_string(), 2), ("second".to_string(), 1)]);
+    }
+}
@@ -2,6 +2,8 @@ use std::{fmt, mem};
 
 use chrono::prelude::{DateTime, Utc};
 
+use crate::agent::answer::{AnswerSegment, parse_answer};
+use crate::agent::budget::DroppedContext;
 use crate::repository::semantic_query::SemanticQuery;
 
 /// A continually updated conversation exchange.
@@ -13,9 +15,15 @@ pub struct Exchange {
     pub id: uuid::Uuid,
     pub query: SemanticQuery<'static>,
     pub answer: Option<String>,
+    /// The answer parsed into segments, with the cited code chunks
+    #[serde(default)]
+    pub answer_segments: Vec<AnswerSegment>,
     pub search_steps: Vec<SearchStep>,
     pub paths: Vec<String>,
     pub code_chunks: Vec<CodeChunk>,
+    /// The context which did not fit in the prompts of the model
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dropped_context: Vec<DroppedContext>,
 
     /// A specifically chosen "focused" code chunk.
     ///
@@ -57,8 +65,16 @@ impl Exchange {
                 _ => panic!("Tried to replace a step that was not found"),
             },
             Update::Article(full_text) => {
+                self.answer_segments = parse_answer(&full_text, &self.paths, &self.code_chunks);
                 *self.answer.get_or_insert_with(String::new) = full_text;
             }
+            Update::DropContext(dropped) => {
+                for it in dropped {
+                    if !self.dropped_context.contains(&it) {
+                        self.dropped_context.push(it);
+                    }
+                }
+            }
             Update::Conclude(conclusion) => {
                 self.response_timestamp = Some(Utc::now());
                 self.conclusion = Some(conclusion);
@@ -187,5 +203,6 @@ pub enum Update {
     StartStep(SearchStep),
     ReplaceStep(SearchStep),
     Article(String),
+    DropContext(Vec<DroppedContext>),
     Conclude(String),
 }
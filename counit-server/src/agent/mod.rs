@@ -1,4 +1,9 @@
 pub mod agent;
+pub mod answer;
+pub mod budget;
+pub mod chat;
+pub mod conversation;
 pub mod exchange;
 pub mod prompts;
+pub mod templates;
 pub mod tools;
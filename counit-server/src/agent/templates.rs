@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// The language of the prompts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptLanguage {
+    #[default]
+    En,
+    Zh,
+}
+
+impl PromptLanguage {
+    pub const ALL: [PromptLanguage; 2] = [PromptLanguage::En, PromptLanguage::Zh];
+
+    fn code(&self) -> &'static str {
+        match self {
+            PromptLanguage::En => "en",
+            PromptLanguage::Zh => "zh",
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Self> {
+        PromptLanguage::ALL.into_iter().find(|it| it.code() == code)
+    }
+}
+
+pub const TOOL_PROMPT: &str = "tool_prompt";
+pub const FILE_EXPLANATION: &str = "file_explanation";
+pub const FINAL_EXPLANATION: &str = "final_explanation";
+pub const HYPOTHETICAL_DOCUMENT: &str = "hypothetical_document";
+pub const EXPLAIN_QUERY: &str = "explain_query";
+pub const CHAT_CONTEXT: &str = "chat_context";
+
+/// The built-in templates, by name and language.
+const DEFAULTS: [(&str, PromptLanguage, &str); 12] = [
+    (TOOL_PROMPT, PromptLanguage::En, include_str!("templates/tool_prompt.en.txt")),
+    (TOOL_PROMPT, PromptLanguage::Zh, include_str!("templates/tool_prompt.zh.txt")),
+    (FILE_EXPLANATION, PromptLanguage::En, include_str!("templates/file_explanation.en.txt")),
+    (FILE_EXPLANATION, PromptLanguage::Zh, include_str!("templates/file_explanation.zh.txt")),
+    (FINAL_EXPLANATION, PromptLanguage::En, include_str!("templates/final_explanation.en.txt")),
+    (FINAL_EXPLANATION, PromptLanguage::Zh, include_str!("templates/final_explanation.zh.txt")),
+    (HYPOTHETICAL_DOCUMENT, PromptLanguage::En, include_str!("templates/hypothetical_document.en.txt")),
+    (HYPOTHETICAL_DOCUMENT, PromptLanguage::Zh, include_str!("templates/hypothetical_document.zh.txt")),
+    (EXPLAIN_QUERY, PromptLanguage::En, include_str!("templates/explain_query.en.txt")),
+    (EXPLAIN_QUERY, PromptLanguage::Zh, include_str!("templates/explain_query.zh.txt")),
+    (CHAT_CONTEXT, PromptLanguage::En, include_str!("templates/chat_context.en.txt")),
+    (CHAT_CONTEXT, PromptLanguage::Zh, include_str!("templates/chat_context.zh.txt")),
+];
+
+/// A template and where it comes from, to list the templates.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TemplateInfo {
+    pub name: String,
+    pub language: PromptLanguage,
+    /// whether it is overridden by a file of the templates directory
+    pub overridden: bool,
+    /// the `{{variables}}` of the template
+    pub variables: Vec<String>,
+}
+
+/// The prompt templates, with `{{variable}}` placeholders. The built-in templates can be
+/// overridden by `<name>.<language>.txt` files of a directory, like `tool_prompt.zh.txt`.
+#[derive(Default)]
+pub struct PromptTemplates {
+    language: PromptLanguage,
+    overrides: HashMap<(String, PromptLanguage), String>,
+}
+
+impl PromptTemplates {
+    /// The built-in templates in the language.
+    pub fn new(language: PromptLanguage) -> Self {
+        PromptTemplates { language, overrides: HashMap::new() }
+    }
+
+    /// Load the overrides of the directory, files which are not named like a template are skipped.
+    pub fn load(dir: &Path, language: PromptLanguage) -> Result<Self> {
+        let mut overrides = HashMap::new();
+        for entry in std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|it| it.to_str()) else {
+                continue;
+            };
+
+            let key = file_name.strip_suffix(".txt")
+                .and_then(|it| it.rsplit_once('.'))
+                .and_then(|(name, code)| Some((name.to_string(), PromptLanguage::from_code(code)?)));
+            match key {
+                Some(key) if DEFAULTS.iter().any(|(name, _, _)| *name == key.0) => {
+                    let template = std::fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                    info!(?path, "prompt template overridden");
+                    overrides.insert(key, template);
+                }
+                _ => warn!(?path, "skipping unknown prompt template"),
+            }
+        }
+
+        Ok(PromptTemplates { language, overrides })
+    }
+
+    pub fn language(&self) -> PromptLanguage {
+        self.language
+    }
+
+    /// The template in the language, the English one if there is none.
+    pub fn template(&self, name: &str, language: PromptLanguage) -> Option<&str> {
+        [language, PromptLanguage::En].into_iter()
+            .find_map(|language| {
+                self.overrides.get(&(name.to_string(), language))
+                    .map(|it| it.as_str())
+                    .or_else(|| DEFAULTS.iter()
+                        .find(|(it, lang, _)| *it == name && *lang == language)
+                        .map(|(_, _, template)| *template))
+            })
+    }
+
+    pub fn list(&self) -> Vec<TemplateInfo> {
+        DEFAULTS.iter()
+            .map(|(name, language, _)| TemplateInfo {
+                name: name.to_string(),
+                language: *language,
+                overridden: self.overrides.contains_key(&(name.to_string(), *language)),
+                variables: variables(self.template(name, *language).unwrap_or_default()),
+            })
+            .collect()
+    }
+
+    /// Render the template in the configured language.
+    pub fn render(&self, name: &str, variables: &[(&str, &str)]) -> String {
+        self.render_in(name, self.language, variables)
+            .unwrap_or_else(|| panic!("unknown prompt template {}", name))
+    }
+
+    /// Render the template in the language, the variables which are not given are left empty.
+    pub fn render_in(&self, name: &str, language: PromptLanguage, variables: &[(&str, &str)]) -> Option<String> {
+        let template = self.template(name, language)?;
+        Some(render(template, |variable| {
+            let value = variables.iter().find(|(name, _)| *name == variable).map(|(_, value)| *value);
+            if value.is_none() {
+                warn!(template = name, variable, "missing prompt variable");
+            }
+            value.unwrap_or_default().to_string()
+        }))
+    }
+}
+
+/// Replace the `{{variable}}` placeholders with their values, the rest of the template is kept
+/// as is, like the braces of JSON.
+fn render(template: &str, mut value: impl FnMut(&str) -> String) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+
+        let variable = rest[start + 2..start + 2 + end].trim();
+        if is_variable(variable) {
+            output.push_str(&rest[..start]);
+            output.push_str(&value(variable));
+        } else {
+            output.push_str(&rest[..start + 2 + end + 2]);
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn variables(template: &str) -> Vec<String> {
+    let mut variables = vec![];
+    render(template, |variable| {
+        if !variables.iter().any(|it| it == variable) {
+            variables.push(variable.to_string());
+        }
+        String::new()
+    });
+    variables
+}
+
+fn is_variable(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|it| it.is_ascii_alphanumeric() || it == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::templates::{EXPLAIN_QUERY, PromptLanguage, PromptTemplates, render, TOOL_PROMPT};
+
+    #[test]
+    fn should_render_variables_and_keep_other_braces() {
+        let rendered = render(r#"{"id": {{ id }}, "value": {{value}}, "raw": "{{not a variable}}"}"#, |it| it.to_uppercase());
+        assert_eq!(rendered, r#"{"id": ID, "value": VALUE, "raw": "{{not a variable}}"}"#);
+        assert_eq!(render("{{unclosed", |_| String::new()), "{{unclosed");
+    }
+
+    #[test]
+    fn should_override_templates_from_directory() {
+        let dir = std::env::temp_dir().join(format!("counit-templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("explain_query.zh.txt"), "问题：{{query}}").unwrap();
+        std::fs::write(dir.join("unknown.en.txt"), "{{query}}").unwrap();
+
+        let templates = PromptTemplates::load(&dir, PromptLanguage::Zh).unwrap();
+        assert_eq!(templates.render(EXPLAIN_QUERY, &[("query", "如何撤销交易")]), "问题：如何撤销交易");
+        assert!(templates.render_in(EXPLAIN_QUERY, PromptLanguage::En, &[("query", "q")]).unwrap().starts_with("Your job"));
+        assert!(templates.render(TOOL_PROMPT, &[]).starts_with("你的任务"));
+        assert!(templates.render_in("unknown", PromptLanguage::En, &[]).is_none());
+
+        let info = templates.list();
+        assert_eq!(info.len(), 12);
+        assert!(info.iter().any(|it| it.name == EXPLAIN_QUERY && it.language == PromptLanguage::Zh && it.overridden));
+        assert_eq!(info.iter().find(|it| it.name == TOOL_PROMPT).unwrap().variables, vec!["tools", "paths"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
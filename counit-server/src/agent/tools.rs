@@ -1,48 +1,208 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::fmt;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// How the tools are given to the model, and how it calls them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolFormat {
+    /// Described in the prompt, called with a JSON list like `["code", "my search query"]`
+    #[default]
+    Text,
+    /// Native function calling of OpenAI compatible apis
+    OpenAi,
+    /// Native tool use of Anthropic apis, only for the definitions of `/api/agent/tools`, the
+    /// agent's client is OpenAI compatible
+    Anthropic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ParameterType {
+    String,
+    IntegerArray,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ToolParameter {
+    /// the name of the argument in function calls, like `query`
+    name: &'static str,
+    /// the name of the argument in the text prompt, like `SEARCH_TERMS`
+    label: &'static str,
+    description: &'static str,
+    parameter_type: ParameterType,
+}
+
+#[derive(Debug, Serialize)]
 pub struct Tool {
+    /// the name in the text prompt, like `codeSearch`
     name: &'static str,
+    /// the name of the call, like `code`
+    call_name: &'static str,
     description: &'static str,
-    schema: &'static str,
+    parameters: Vec<ToolParameter>,
     examples: &'static str,
 }
 
+impl Tool {
+    /// The text schema, like `{"name": "code", "args": [SEARCH_TERMS // str]}`.
+    fn text_schema(&self) -> String {
+        let args: Vec<String> = self.parameters.iter()
+            .map(|it| {
+                let kind = match it.parameter_type {
+                    ParameterType::String => "str",
+                    ParameterType::IntegerArray => "int[]",
+                };
+                format!("{} // {}", it.label, kind)
+            })
+            .collect();
+        format!("{{\"name\": \"{}\", \"args\": [{}]}}", self.call_name, args.join(", "))
+    }
+
+    /// The JSON schema of the arguments.
+    fn parameters_schema(&self) -> Value {
+        let properties: serde_json::Map<String, Value> = self.parameters.iter()
+            .map(|it| {
+                let schema = match it.parameter_type {
+                    ParameterType::String => json!({ "type": "string", "description": it.description }),
+                    ParameterType::IntegerArray => json!({
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "description": it.description,
+                    }),
+                };
+                (it.name.to_string(), schema)
+            })
+            .collect();
+        let required: Vec<&str> = self.parameters.iter().map(|it| it.name).collect();
+
+        json!({ "type": "object", "properties": properties, "required": required })
+    }
+
+    /// The definition of the tool for native function calling, none for the text format whose
+    /// tools are described in the prompt.
+    pub fn function_schema(&self, format: ToolFormat) -> Option<Value> {
+        match format {
+            ToolFormat::Text => None,
+            ToolFormat::OpenAi => Some(json!({
+                "type": "function",
+                "function": {
+                    "name": self.call_name,
+                    "description": self.description,
+                    "parameters": self.parameters_schema(),
+                }
+            })),
+            ToolFormat::Anthropic => Some(json!({
+                "name": self.call_name,
+                "description": self.description,
+                "input_schema": self.parameters_schema(),
+            })),
+        }
+    }
+}
+
 impl fmt::Display for Tool {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(f, "{}:", self.name)?;
         writeln!(f, "\tdescription: {}", self.description)?;
-        writeln!(f, "\tschema: {}", self.schema)?;
+        writeln!(f, "\tschema: {}", self.text_schema())?;
         writeln!(f, "\texamples: {:?}", self.examples)?;
         Ok(())
     }
 }
 
-pub fn tools_list() -> [Tool; 4] {
-    [
+const SEARCH_TERMS: &str = "SEARCH_TERMS";
+const PATH_ALIASES: &str = "ARRAY_OF_PATH_ALIASES";
+
+pub fn tools_list() -> Vec<Tool> {
+    vec![
         Tool {
             name: "codeSearch",
+            call_name: "code",
             description: "Search the contents of files in a codebase semantically. Results will not necessarily match search terms exactly, but should be related.",
-            schema: "{\"name\": \"code\", \"args\": [SEARCH_TERMS // str]}",
-            examples: "[[\"code\", \"backend error types\"], [\"code\", \"react functional components\"]]"
+            parameters: vec![ToolParameter {
+                name: "query",
+                label: SEARCH_TERMS,
+                description: "The query with which to search. This should consist of keywords that might match something in the codebase, e.g. 'react functional components', 'contextmanager', 'bearer token'",
+                parameter_type: ParameterType::String,
+            }],
+            examples: "[[\"code\", \"backend error types\"], [\"code\", \"react functional components\"]]",
         },
         Tool {
             name: "pathSearch",
+            call_name: "path",
             description: "Search the pathnames in a codebase. Results may not be exact matches, but will be similar by some edit-distance. Use when you want to find a specific file or directory.",
-            schema: "{\"name\": \"path\", \"args\": [SEARCH_TERMS // str]}",
-            examples: "[[\"path\", \"server/src\"], [\"path\", \".tsx\"], [\"path\", \"examples/android\"]]"
+            parameters: vec![ToolParameter {
+                name: "query",
+                label: SEARCH_TERMS,
+                description: "The query with which to search. This should consist of keywords that might match a path, e.g. 'server/src'.",
+                parameter_type: ParameterType::String,
+            }],
+            examples: "[[\"path\", \"server/src\"], [\"path\", \".tsx\"], [\"path\", \"examples/android\"]]",
         },
         Tool {
             name: "processFiles",
+            call_name: "proc",
             description: "Read one or more files and extract the line ranges which are relevant to the search terms.",
-            schema: "{\"name\": \"proc\", \"args\": [SEARCH_TERMS // str, ARRAY_OF_PATH_ALIASES // int[]}",
-            examples: "[[\"proc\", \"find all the functional react components\", [2,5]], [\"proc\", \"where are error types\", [0]], [\"proc\", \"gitoxide initialisation\", [2,5,8]]]"
+            parameters: vec![
+                ToolParameter {
+                    name: "query",
+                    label: SEARCH_TERMS,
+                    description: "The query with which to search the files.",
+                    parameter_type: ParameterType::String,
+                },
+                ToolParameter {
+                    name: "paths",
+                    label: PATH_ALIASES,
+                    description: "The aliases of the paths to read.",
+                    parameter_type: ParameterType::IntegerArray,
+                },
+            ],
+            examples: "[[\"proc\", \"find all the functional react components\", [2,5]], [\"proc\", \"where are error types\", [0]], [\"proc\", \"gitoxide initialisation\", [2,5,8]]]",
         },
         Tool {
             name: "none",
+            call_name: "none",
             description: "You have enough information to answer the user's query. This is the final step, and signals that you have enough information to respond to the user's query. ARRAY_OF_PATH_ALIASES contains the aliases of the paths which are particularly relevant to the query.",
-            schema: "{\"name\": \"none\", \"args\": [ARRAY_OF_PATH_ALIASES // int[]]}",
-            examples: "[[\"none\", [1]], [\"none\", [3,5]], [\"none\", []]]"
-        }]
+            parameters: vec![ToolParameter {
+                name: "paths",
+                label: PATH_ALIASES,
+                description: "The aliases of the paths to answer with respect to. Can be empty if the answer is not related to a specific path.",
+                parameter_type: ParameterType::IntegerArray,
+            }],
+            examples: "[[\"none\", [1]], [\"none\", [3,5]], [\"none\", []]]",
+        },
+    ]
+}
+
+/// The definitions of every tool for native function calling, in the format of the provider.
+pub fn function_schemas(format: ToolFormat) -> Value {
+    Value::Array(tools_list().iter().filter_map(|it| it.function_schema(format)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::tools::{function_schemas, ToolFormat, tools_list};
+
+    #[test]
+    fn should_render_text_schema() {
+        let tools = tools_list();
+        assert_eq!(tools[0].text_schema(), r#"{"name": "code", "args": [SEARCH_TERMS // str]}"#);
+        assert_eq!(tools[2].text_schema(), r#"{"name": "proc", "args": [SEARCH_TERMS // str, ARRAY_OF_PATH_ALIASES // int[]]}"#);
+        assert!(tools[3].to_string().starts_with("none:\n\tdescription: You have enough information"));
+    }
+
+    #[test]
+    fn should_render_function_schemas() {
+        let openai = function_schemas(ToolFormat::OpenAi);
+        assert_eq!(openai[2]["type"], "function");
+        assert_eq!(openai[2]["function"]["name"], "proc");
+        assert_eq!(openai[2]["function"]["parameters"]["required"], serde_json::json!(["query", "paths"]));
+        assert_eq!(openai[2]["function"]["parameters"]["properties"]["paths"]["items"]["type"], "integer");
+
+        let anthropic = function_schemas(ToolFormat::Anthropic);
+        assert_eq!(anthropic[0]["name"], "code");
+        assert_eq!(anthropic[0]["input_schema"]["properties"]["query"]["type"], "string");
+
+        assert_eq!(function_schemas(ToolFormat::Text), serde_json::json!([]));
+    }
 }
@@ -1,8 +1,19 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use anyhow::{bail, Result};
-use tracing::warn;
+use tracing::{info, warn};
+use crate::agent::conversation::ConversationStore;
+use crate::agent::templates::PromptTemplates;
 use crate::configuration::Configuration;
 use crate::domain::domain_transpiler::DomainTranspiler;
+use crate::domain::glossary_watcher;
+use crate::dsl::example_library::ExampleLibrary;
+use crate::graph::graph_store::GraphStore;
+use crate::llm::model::{Model, ModelStore, ModelSummary};
+use crate::llm::tokens::TokenCounter;
+use crate::model::openapi_document::OpenApiStore;
+use crate::repository::path_index::PathIndex;
 use crate::repository::semantic::Semantic;
 
 #[derive(Clone)]
@@ -10,14 +21,41 @@ pub struct Application {
     /// User-provided configuration
     pub config: Arc<Configuration>,
 
+    /// The file of the configuration, none with the development defaults
+    pub config_file: Option<PathBuf>,
+
     pub transpiler: Arc<DomainTranspiler>,
 
+    /// Service and data map relations reported by ArchGuard
+    pub graph: Arc<GraphStore>,
+
+    /// OpenAPI documents generated from the analysed code
+    pub openapi: Arc<OpenApiStore>,
+
+    /// Relative paths of the ingested code, for path search
+    pub paths: Arc<PathIndex>,
+
     /// Semantic search subsystem
     pub(crate) semantic: Option<Semantic>,
+
+    /// Conversation threads of the agent
+    pub conversations: Arc<ConversationStore>,
+
+    /// Few-shot examples of the explain prompt
+    pub examples: Arc<ExampleLibrary>,
+
+    /// Templates of the prompts
+    pub templates: Arc<PromptTemplates>,
+
+    /// Counts the tokens of the prompts, to fit them in the context window of the model
+    pub tokens: Arc<TokenCounter>,
+
+    /// The chat model, which is replaced when the configuration is reloaded
+    pub(crate) model: Arc<ModelStore>,
 }
 
 impl Application {
-    pub async fn initialize(mut config: Configuration) -> Result<Application> {
+    pub async fn initialize(mut config: Configuration, config_file: Option<PathBuf>) -> Result<Application> {
         let config = Arc::new(config);
 
         let semantic = match config.qdrant_url {
@@ -42,10 +80,99 @@ impl Application {
             transpiler = Arc::new(DomainTranspiler::empty());
         };
 
+        if config.domain_language_watch {
+            if let Err(err) = glossary_watcher::watch(Arc::clone(&transpiler)) {
+                warn!("Glossary hot reload disabled: {:#}", err);
+            }
+        }
+
+        let model = Model::from_config(&config)?;
+        if model.client.is_none() {
+            warn!("LLM features disabled because `llm_base_url` is not provided. Starting without.");
+        }
+
+        let conversations = match config.conversation_dir {
+            Some(ref dir) => ConversationStore::load(dir)?,
+            None => ConversationStore::default(),
+        };
+
+        let examples = match config.explain_example_dir {
+            Some(ref dir) => ExampleLibrary::load(dir)?,
+            None => ExampleLibrary::default(),
+        };
+
+        let templates = match config.prompt_template_dir {
+            Some(ref dir) => PromptTemplates::load(dir, config.prompt_language)?,
+            None => PromptTemplates::new(config.prompt_language),
+        };
+
+        let tokens = TokenCounter::load(config.llm_tokenizer.as_deref(), &config.model_dir);
+
+        // the index is in memory, the paths of the repos ingested before the restart are in Qdrant,
+        // the repos ingested again while they are loaded are skipped
+        let paths = Arc::new(PathIndex::default());
+        if let Some(ref semantic) = semantic {
+            let (semantic, paths) = (semantic.clone(), Arc::clone(&paths));
+            tokio::spawn(async move {
+                match semantic.code_paths().await {
+                    Ok(found) => {
+                        let mut by_repo: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+                        for (repo, path) in &found {
+                            by_repo.entry(repo).or_default().push(path);
+                        }
+                        for (repo, repo_paths) in by_repo {
+                            paths.seed(repo, repo_paths);
+                        }
+                        info!(paths = found.len(), "path index loaded from Qdrant");
+                    }
+                    Err(err) => warn!("Failed to load the path index from Qdrant: {:#}", err),
+                }
+            });
+        }
+
         Ok(Application {
             config,
+            config_file,
             transpiler,
+            graph: Arc::new(GraphStore::default()),
+            openapi: Arc::new(OpenApiStore::default()),
+            conversations: Arc::new(conversations),
+            examples: Arc::new(examples),
+            templates: Arc::new(templates),
+            tokens: Arc::new(tokens),
+            paths,
             semantic,
+            model: Arc::new(ModelStore::new(model)),
         })
     }
+
+    /// Read the `llm_*` settings of the configuration file again and replace the model, the
+    /// requests in flight keep the previous one. The other settings need a restart.
+    pub fn reload_model(&self, config_file: &Path) -> Result<ModelSummary> {
+        let config = Configuration::from_file(config_file)?;
+        let model = Model::from_config(&config)?;
+        let summary = model.summary();
+        self.model.replace(model);
+        info!(model = summary.name, enabled = summary.enabled, "model reloaded");
+        Ok(summary)
+    }
+
+    /// An application with empty stores and without semantic search, for the tests.
+    #[cfg(test)]
+    pub(crate) fn for_tests(config: Configuration, model: Model) -> Application {
+        Application {
+            config: Arc::new(config),
+            config_file: None,
+            transpiler: Arc::new(DomainTranspiler::empty()),
+            graph: Arc::new(GraphStore::default()),
+            openapi: Arc::new(OpenApiStore::default()),
+            conversations: Arc::new(ConversationStore::default()),
+            examples: Arc::new(ExampleLibrary::default()),
+            templates: Arc::new(PromptTemplates::default()),
+            tokens: Arc::new(TokenCounter::Estimate),
+            paths: Arc::new(PathIndex::default()),
+            semantic: None,
+            model: Arc::new(ModelStore::new(model)),
+        }
+    }
 }
\ No newline at end of file
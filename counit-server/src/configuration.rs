@@ -1,6 +1,11 @@
 use std::path::{Path, PathBuf};
 
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::agent::templates::PromptLanguage;
+use crate::agent::tools::ToolFormat;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Configuration {
@@ -22,6 +27,58 @@ pub struct Configuration {
     #[serde(default = "default_domain_language_dir")]
     /// Path to the domain language directory, supported format: .csv, .json
     pub domain_language_dir: Option<PathBuf>,
+
+    #[serde(default = "default_domain_language_watch")]
+    /// Reload the glossary when the files of `domain_language_dir` change
+    pub domain_language_watch: bool,
+
+    /// Base URL of an OpenAI compatible chat completions api, like `https://api.openai.com/v1`,
+    /// the agent is disabled if it is not provided
+    pub llm_base_url: Option<String>,
+
+    #[serde(default = "default_llm_model")]
+    pub llm_model: String,
+
+    /// Not serialized, the configuration is returned by `/api/health`
+    #[serde(skip_serializing)]
+    pub llm_api_key: Option<String>,
+
+    #[serde(default)]
+    /// How the agent's tools are given to the model: `text` in the prompt, or with the native
+    /// function calling of `openai`
+    pub llm_tool_format: ToolFormat,
+
+    #[serde(default = "default_llm_context_window")]
+    /// Tokens of the context window of the model, the prompts are cut to fit in it
+    pub llm_context_window: usize,
+
+    #[serde(default = "default_llm_max_completion_tokens")]
+    /// Tokens of the context window kept for the completion of the model, sent as `max_tokens`
+    pub llm_max_completion_tokens: usize,
+
+    /// Path to the `tokenizer.json` to count the tokens of the prompts, the tokenizer of the
+    /// embedding model is used if it is not provided
+    pub llm_tokenizer: Option<PathBuf>,
+
+    #[serde(default = "default_agent_max_steps")]
+    /// Max number of tool calls of the agent before it has to answer
+    pub agent_max_steps: usize,
+
+    /// Path to the directory of the agent's conversation threads, they are only kept in memory
+    /// if it is not provided
+    pub conversation_dir: Option<PathBuf>,
+
+    /// Path to the directory of the few-shot examples of the explain prompt, one JSON file for
+    /// each example, the built-in examples are only kept in memory if it is not provided
+    pub explain_example_dir: Option<PathBuf>,
+
+    #[serde(default)]
+    /// The language of the prompts, `en` or `zh`
+    pub prompt_language: PromptLanguage,
+
+    /// Path to the directory of the prompt templates which override the built-in ones, named
+    /// like `tool_prompt.zh.txt`
+    pub prompt_template_dir: Option<PathBuf>,
 }
 
 const fn default_port() -> u16 {
@@ -40,10 +97,40 @@ fn default_domain_language_dir() -> Option<PathBuf> {
     Some("domain".into())
 }
 
+const fn default_domain_language_watch() -> bool {
+    true
+}
+
+fn default_llm_model() -> String {
+    String::from("gpt-3.5-turbo")
+}
+
+const fn default_llm_context_window() -> usize {
+    8192
+}
+
+const fn default_llm_max_completion_tokens() -> usize {
+    1024
+}
+
+const fn default_agent_max_steps() -> usize {
+    10
+}
+
+/// The configuration file, read at startup and when the model is reloaded.
+pub const CONFIG_FILE: &str = "public/config.json";
+
 impl Configuration {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("invalid configuration {}", path.display()))
+    }
+
     pub fn default() -> Self {
         let project_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("public");
-        println!("project_dir: {:?}", project_dir);
+        // stdout carries the messages of `--mcp-stdio`
+        info!("project_dir: {:?}", project_dir);
 
         Configuration {
             host: "0.0.0.0".to_string(),
@@ -52,6 +139,19 @@ impl Configuration {
             qdrant_url: Some("http://127.0.0.1:6334".into()),
             model_dir: project_dir.join("model"),
             domain_language_dir: Some(project_dir.join("domain")),
+            domain_language_watch: default_domain_language_watch(),
+            llm_base_url: None,
+            llm_model: default_llm_model(),
+            llm_api_key: None,
+            llm_tool_format: ToolFormat::default(),
+            llm_context_window: default_llm_context_window(),
+            llm_max_completion_tokens: default_llm_max_completion_tokens(),
+            llm_tokenizer: None,
+            agent_max_steps: default_agent_max_steps(),
+            conversation_dir: None,
+            explain_example_dir: None,
+            prompt_language: PromptLanguage::default(),
+            prompt_template_dir: None,
         }
     }
 }
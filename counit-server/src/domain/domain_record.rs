@@ -2,6 +2,25 @@
 pub struct DomainRecord {
     pub(crate) native: String,
     pub(crate) english: String,
+    #[serde(default)]
     pub(crate) abbreviation: String,
+    #[serde(default)]
     pub(crate) description: String,
 }
+
+impl DomainRecord {
+    /// Trim the whitespace and the `|` padding of the fields, as left by the glossaries which were
+    /// copied from a markdown table, returns whether a field changed.
+    pub fn normalize(&mut self) -> bool {
+        let mut changed = false;
+        for field in [&mut self.native, &mut self.english, &mut self.abbreviation, &mut self.description] {
+            let normalized = field.trim_matches(|it: char| it.is_whitespace() || it == '|');
+            if normalized.len() != field.len() {
+                *field = normalized.to_string();
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
@@ -1,100 +1,350 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use thiserror::Error;
 
 use crate::domain::domain_record::DomainRecord;
+use crate::domain::glossary_loader;
+use crate::domain::glossary_loader::{Diagnostic, LoadReport, Severity};
+use crate::domain::term_matcher::{MatchedTerm, TermMatcher};
+
+/// The file of the directory where the records created by the api are written.
+const NEW_RECORDS_FILE: &str = "glossary.csv";
 
+/// The glossary of the domain: the records of the CSV and JSON files of the domain language
+/// directory, which can be edited, the changes are written back to the file of the record.
 pub struct DomainTranspiler {
-    pub domain_records: Vec<DomainRecord>,
+    dir: Option<PathBuf>,
+    glossary: RwLock<Glossary>,
+    /// Held while the files are written or loaded, so that a reload doesn't replace an edit with
+    /// the files it read before the edit. The requests only wait for the glossary.
+    files: Mutex<()>,
+}
+
+#[derive(Default)]
+struct Glossary {
+    entries: Vec<GlossaryEntry>,
+    /// The terms of the records, rebuilt when they change
+    matcher: TermMatcher,
+    /// What was skipped when the files were loaded
+    report: LoadReport,
+}
+
+#[derive(Clone)]
+struct GlossaryEntry {
+    record: DomainRecord,
+    /// the file of the record, none if it is only in memory
+    source: Option<PathBuf>,
+}
+
+impl Glossary {
+    fn new(entries: Vec<GlossaryEntry>, report: LoadReport) -> Self {
+        let mut glossary = Glossary { entries, matcher: TermMatcher::default(), report };
+        glossary.rebuild_matcher();
+        glossary
+    }
+
+    fn rebuild_matcher(&mut self) {
+        let records: Vec<DomainRecord> = self.entries.iter().map(|it| it.record.clone()).collect();
+        self.matcher = TermMatcher::new(&records);
+    }
+
+    fn position(&self, native: &str) -> Option<usize> {
+        self.entries.iter().position(|it| it.record.native == native)
+    }
+
+    /// Check that the record has a native word and an English name, and that its native word and
+    /// abbreviation are not the ones of another record.
+    fn validate(&self, record: &DomainRecord, replaced: Option<usize>) -> Result<(), GlossaryError> {
+        if record.native.trim().is_empty() || record.english.trim().is_empty() {
+            return Err(GlossaryError::Invalid("`native` and `english` are required".to_string()));
+        }
+
+        let others = self.entries.iter().enumerate()
+            .filter(|(index, _)| Some(*index) != replaced)
+            .map(|(_, it)| &it.record);
+        for other in others {
+            if other.native == record.native {
+                return Err(GlossaryError::Duplicate(format!("term {} already exists", record.native)));
+            }
+            if has_abbreviation(record) && other.abbreviation == record.abbreviation {
+                return Err(GlossaryError::Duplicate(format!(
+                    "abbreviation {} is already used by {}", record.abbreviation, other.native
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GlossaryError {
+    #[error("{0}")]
+    Invalid(String),
+
+    #[error("{0}")]
+    Duplicate(String),
+
+    #[error("term {0} not found")]
+    NotFound(String),
+
+    /// Writing the file would delete the records which were skipped when it was loaded.
+    #[error("{} has records which were skipped when it was loaded, fix them before editing it", .0.display())]
+    SkippedRecords(PathBuf),
+
+    #[error("failed to save the glossary: {error}")]
+    Persistence {
+        #[from]
+        error: anyhow::Error,
+    },
+}
+
+/// A text with the terms of the glossary annotated with their English names.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Transpiled {
+    pub text: String,
+    /// the terms found, with their offsets in the source text
+    pub terms: Vec<MatchedTerm>,
 }
 
 impl DomainTranspiler {
     pub fn empty() -> Self {
         DomainTranspiler {
-            domain_records: Vec::new(),
+            dir: None,
+            glossary: RwLock::new(Glossary::default()),
+            files: Mutex::new(()),
         }
     }
 
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
-        let mut transpiler = DomainTranspiler {
-            domain_records: Vec::new(),
-        };
+        let mut transpiler = DomainTranspiler::empty();
+        transpiler.dir = Some(path.as_ref().to_path_buf());
 
         // check path is exists
         if !path.as_ref().exists() {
             return transpiler;
         }
 
-        transpiler.load(path);
+        let (entries, report) = load(path.as_ref());
+        transpiler.glossary = RwLock::new(Glossary::new(entries, report));
 
         return transpiler;
     }
 
-    fn load<P: AsRef<Path>>(&mut self, path: P) {
-        use walkdir::WalkDir;
-        for entry in WalkDir::new(path) {
-            let entry = entry.unwrap();
-            if entry.file_type().is_file() {
-                let path_str = entry.path().to_str();
-                let ext = entry.path().extension();
+    /// The domain language directory, none if the glossary is only in memory.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
 
-                if path_str.is_none() || ext.is_none() {
+    /// Load the files of the directory again. The new glossary is built before it replaces the
+    /// previous one at once, so that the requests see either of them. The previous records of
+    /// the files which can't be loaded at all, like a file being written, are kept.
+    pub fn reload(&self) -> anyhow::Result<LoadReport> {
+        let Some(dir) = &self.dir else {
+            bail!("the glossary has no `domain_language_dir` to reload");
+        };
+
+        let _files = self.files.lock().unwrap();
+        let (mut entries, mut report) = load(dir);
+        {
+            let previous = self.glossary.read().unwrap();
+            for file in report.files.iter_mut().filter(|it| !it.loaded) {
+                let kept: Vec<GlossaryEntry> = previous.entries.iter()
+                    .filter(|it| it.source.as_deref() == Some(file.path.as_path()))
+                    .cloned()
+                    .collect();
+                if kept.is_empty() {
                     continue;
                 }
 
-                let ext = ext.unwrap().to_str().unwrap();
-                match ext {
-                    "csv" => {
-                        let _ = self.load_csv(path_str.unwrap());
-                    }
-                    "json" => {
-                        self.load_json(path_str.unwrap());
-                    }
-                    _ => {}
-                }
+                file.diagnostics.push(Diagnostic {
+                    line: None,
+                    severity: Severity::Info,
+                    message: format!("kept the {} records loaded before", kept.len()),
+                });
+                file.records = kept.len();
+                report.records += kept.len();
+                entries.extend(kept);
             }
         }
+
+        *self.glossary.write().unwrap() = Glossary::new(entries, report.clone());
+        Ok(report)
+    }
+
+    /// What was skipped when the files were last loaded.
+    pub fn report(&self) -> LoadReport {
+        self.glossary.read().unwrap().report.clone()
+    }
+
+    /// The records, in the order of their files.
+    pub fn records(&self) -> Vec<DomainRecord> {
+        self.glossary.read().unwrap().entries.iter().map(|it| it.record.clone()).collect()
+    }
+
+    pub fn get(&self, native: &str) -> Option<DomainRecord> {
+        let glossary = self.glossary.read().unwrap();
+        glossary.position(native).map(|index| glossary.entries[index].record.clone())
     }
 
-    fn load_csv(&mut self, path: &str) -> anyhow::Result<()> {
-        csv::Reader::from_path(path)?
-            .into_deserialize()
-            .for_each(|result| {
-                let record: DomainRecord = result.unwrap();
-                self.domain_records.push(record);
-            });
+    /// The records with the text in a field, ignoring the case.
+    pub fn search(&self, text: &str) -> Vec<DomainRecord> {
+        let text = text.to_lowercase();
+        self.glossary.read().unwrap().entries.iter()
+            .map(|it| &it.record)
+            .filter(|record| {
+                [&record.native, &record.english, &record.abbreviation, &record.description].iter()
+                    .any(|field| field.to_lowercase().contains(&text))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Add the record to the glossary, in the [NEW_RECORDS_FILE] of the directory.
+    pub fn create(&self, mut record: DomainRecord) -> Result<DomainRecord, GlossaryError> {
+        record.normalize();
+        let _files = self.files.lock().unwrap();
+        let mut glossary = self.glossary.write().unwrap();
+        glossary.validate(&record, None)?;
+
+        let source = self.dir.as_ref().map(|dir| dir.join(NEW_RECORDS_FILE));
+        let mut entries = glossary.entries.clone();
+        entries.push(GlossaryEntry { record: record.clone(), source: source.clone() });
+        self.save(&mut glossary, entries, source.as_deref())?;
+        Ok(record)
+    }
+
+    /// Replace the record of the native word, which may be renamed.
+    pub fn update(&self, native: &str, mut record: DomainRecord) -> Result<DomainRecord, GlossaryError> {
+        record.normalize();
+        let _files = self.files.lock().unwrap();
+        let mut glossary = self.glossary.write().unwrap();
+        let index = glossary.position(native).ok_or_else(|| GlossaryError::NotFound(native.to_string()))?;
+        glossary.validate(&record, Some(index))?;
+
+        let mut entries = glossary.entries.clone();
+        entries[index].record = record.clone();
+        let source = entries[index].source.clone();
+        self.save(&mut glossary, entries, source.as_deref())?;
+        Ok(record)
+    }
 
+    pub fn delete(&self, native: &str) -> Result<(), GlossaryError> {
+        let _files = self.files.lock().unwrap();
+        let mut glossary = self.glossary.write().unwrap();
+        let index = glossary.position(native).ok_or_else(|| GlossaryError::NotFound(native.to_string()))?;
+
+        let mut entries = glossary.entries.clone();
+        let entry = entries.remove(index);
+        self.save(&mut glossary, entries, entry.source.as_deref())?;
         Ok(())
     }
 
-    fn load_json(&mut self, path: &str) {
-        let file = std::fs::File::open(path).unwrap();
-        let reader = std::io::BufReader::new(file);
-        let records: Vec<DomainRecord> = serde_json::from_reader(reader).unwrap();
-        records.into_iter().for_each(|record| {
-            self.domain_records.push(record);
-        });
+    /// Write the records of the changed file, then replace the records of the glossary, which are
+    /// left as they were if the file can't be written. The file is only written from its records
+    /// if none of them were skipped when it was loaded.
+    fn save(&self, glossary: &mut Glossary, entries: Vec<GlossaryEntry>, source: Option<&Path>) -> Result<(), GlossaryError> {
+        if let Some(source) = source {
+            if glossary.report.files.iter().any(|it| it.path == source && it.has_problems()) {
+                return Err(GlossaryError::SkippedRecords(source.to_path_buf()));
+            }
+
+            let records: Vec<&DomainRecord> = entries.iter()
+                .filter(|it| it.source.as_deref() == Some(source))
+                .map(|it| &it.record)
+                .collect();
+            write_records(source, &records)?;
+        }
+
+        glossary.entries = entries;
+        glossary.rebuild_matcher();
+        Ok(())
     }
 
     // replace the domain words in the source, for example 金融 -> 金融(finance)
     pub fn transpile(&self, source: &str) -> String {
-        let mut result = source.to_string();
-        for record in &self.domain_records {
-            let native = record.native.as_str();
-            let english = record.english.as_str();
-            let abbreviation = record.abbreviation.as_str();
-            let description = record.description.as_str();
-
-            result = result.replace(native, &format!("{}({})", native, english));
-            if !abbreviation.is_empty() {
-                result = result.replace(abbreviation, &format!("{}({})", abbreviation, english));
-            }
+        self.annotate(source).text
+    }
+
+    /// Annotate the terms of the source in a single pass, so that the annotations are not matched
+    /// again. The terms which are already annotated, like in a transpiled text, are kept as is.
+    pub fn annotate(&self, source: &str) -> Transpiled {
+        let terms = self.glossary.read().unwrap().matcher.find(source);
 
-            if !description.is_empty() {
-                result = result.replace(description, &format!("{}({})", description, english));
+        let mut text = String::with_capacity(source.len());
+        let mut copied = 0;
+        for term in &terms {
+            text.push_str(&source[copied..term.end]);
+            copied = term.end;
+
+            let annotation = format!("({})", term.english);
+            if !term.english.is_empty() && !source[term.end..].starts_with(&annotation) {
+                text.push_str(&annotation);
             }
         }
+        text.push_str(&source[copied..]);
+
+        Transpiled { text, terms }
+    }
+
+    /// The records whose native word, English name or abbreviation appear in the text.
+    pub fn lookup(&self, text: &str) -> Vec<DomainRecord> {
+        let lowercase = text.to_lowercase();
+        self.glossary.read().unwrap().entries.iter()
+            .map(|it| &it.record)
+            .filter(|record| {
+                (!record.native.is_empty() && text.contains(record.native.as_str()))
+                    || (has_abbreviation(record) && text.contains(record.abbreviation.as_str()))
+                    || (!record.english.is_empty() && lowercase.contains(&record.english.to_lowercase()))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// `-` is used in the glossaries for no abbreviation.
+fn has_abbreviation(record: &DomainRecord) -> bool {
+    !matches!(record.abbreviation.trim(), "" | "-")
+}
 
-        result
+/// The records of the directory, with their files.
+fn load(dir: &Path) -> (Vec<GlossaryEntry>, LoadReport) {
+    let (records, report) = glossary_loader::load(dir);
+    let entries = records.into_iter()
+        .map(|(record, source)| GlossaryEntry { record, source: Some(source) })
+        .collect();
+    (entries, report)
+}
+
+/// Write the records in the format of the file, CSV unless it is a `.json` file. They are written
+/// to a temporary file which replaces the file, so that a failed write doesn't leave it truncated.
+fn write_records(path: &Path, records: &[&DomainRecord]) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
     }
+
+    let content = if path.extension().and_then(|it| it.to_str()) == Some("json") {
+        serde_json::to_string_pretty(records)?
+    } else {
+        // the header is written even without records, so that the file can be edited
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
+        writer.write_record(["native", "english", "abbreviation", "description"])?;
+        for record in records {
+            writer.serialize(record)?;
+        }
+        String::from_utf8(writer.into_inner()?)?
+    };
+
+    let file_name = path.file_name().and_then(|it| it.to_str()).unwrap_or("glossary");
+    let temp = path.with_file_name(format!(".{}.tmp", file_name));
+    std::fs::write(&temp, content).with_context(|| format!("failed to write {}", temp.display()))?;
+    std::fs::rename(&temp, path).with_context(|| {
+        let _ = std::fs::remove_file(&temp);
+        format!("failed to replace {}", path.display())
+    })
 }
 
 #[cfg(test)]
@@ -102,6 +352,7 @@ mod tests {
     use std::path::{Path, PathBuf};
 
     use super::*;
+    use crate::domain::term_matcher::TermKind;
 
     #[test]
     fn load_csv() {
@@ -109,7 +360,7 @@ mod tests {
 
 
         let mut loader = DomainTranspiler::new(model_dir);
-        assert_eq!(loader.domain_records.len(), 29);
+        assert_eq!(loader.records().len(), 29);
     }
 
     fn domain_dir() -> PathBuf {
@@ -125,9 +376,146 @@ mod tests {
         let model_dir = domain_dir();
 
         let mut loader = DomainTranspiler::new(model_dir);
-        assert_eq!(loader.domain_records.len(), 29);
+        assert_eq!(loader.records().len(), 29);
 
         assert_eq!(loader.transpile("本币"), "本币(Domestic Currency)");
         assert_eq!(loader.transpile("DCY"), "DCY(Domestic Currency)");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn transpile_longest_terms_once() {
+        let loader = DomainTranspiler::new(domain_dir());
+
+        assert_eq!(loader.transpile("资产配置"), "资产配置(Asset Allocation)");
+        assert_eq!(loader.transpile("客户资产和资产"), "客户资产(Client Assets)和资产(Asset)");
+        assert_eq!(loader.transpile("本币(Domestic Currency)"), "本币(Domestic Currency)");
+        assert_eq!(loader.transpile("本币 - DCY"), "本币(Domestic Currency) - DCY(Domestic Currency)");
+        // abbreviations only match whole words
+        assert_eq!(loader.transpile("CPU of the USER"), "CPU of the USER");
+        assert_eq!(loader.transpile("CP,IR"), "CP(Currency Pair),IR(Interest Rate)");
+    }
+
+    #[test]
+    fn annotate_terms_with_offsets() {
+        let loader = DomainTranspiler::new(domain_dir());
+
+        let transpiled = loader.annotate("查询本币汇率");
+        let terms: Vec<(&str, &str, usize, usize)> = transpiled.terms.iter()
+            .map(|it| (it.term.as_str(), it.english.as_str(), it.start, it.end))
+            .collect();
+        assert_eq!(terms, vec![("本币", "Domestic Currency", 6, 12), ("汇率", "Exchange Rate", 12, 18)]);
+        assert_eq!(transpiled.text, "查询本币(Domestic Currency)汇率(Exchange Rate)");
+        assert_eq!(transpiled.terms[1].kind, TermKind::Native);
+    }
+
+    #[test]
+    fn lookup() {
+        let loader = DomainTranspiler::new(domain_dir());
+
+        let records = loader.lookup("本币 and the domestic currency");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].abbreviation, "DCY");
+        assert!(loader.lookup("nothing here").is_empty());
+    }
+
+    fn record(native: &str, english: &str, abbreviation: &str) -> DomainRecord {
+        DomainRecord {
+            native: native.to_string(),
+            english: english.to_string(),
+            abbreviation: abbreviation.to_string(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn edit_and_persist_records() {
+        let dir = std::env::temp_dir().join(format!("counit-glossary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("payment.json"), r#"[{"native": "收单", "english": "Acquiring", "abbreviation": "-", "description": ""}]"#).unwrap();
+
+        let transpiler = DomainTranspiler::new(&dir);
+        transpiler.create(record("本币", "Domestic Currency", "DCY")).unwrap();
+        assert!(matches!(transpiler.create(record("本币", "Local Currency", "")), Err(GlossaryError::Duplicate(_))));
+        assert!(matches!(transpiler.create(record("外币", "Foreign Currency", "DCY")), Err(GlossaryError::Duplicate(_))));
+        assert!(matches!(transpiler.create(record("外币", "", "")), Err(GlossaryError::Invalid(_))));
+        assert_eq!(transpiler.transpile("本币收单"), "本币(Domestic Currency)收单(Acquiring)");
+
+        transpiler.update("收单", record("统一收单", "Unified Acquiring", "-")).unwrap();
+        assert!(matches!(transpiler.update("收单", record("收单", "Acquiring", "")), Err(GlossaryError::NotFound(_))));
+        transpiler.delete("本币").unwrap();
+        assert_eq!(transpiler.transpile("本币"), "本币");
+        assert_eq!(transpiler.search("acquiring").len(), 1);
+
+        let reloaded = DomainTranspiler::new(&dir);
+        let natives: Vec<String> = reloaded.records().into_iter().map(|it| it.native).collect();
+        assert_eq!(natives, vec!["统一收单"]);
+        assert_eq!(std::fs::read_to_string(dir.join("glossary.csv")).unwrap(), "native,english,abbreviation,description\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keep_records_when_the_file_cant_be_written() {
+        let dir = std::env::temp_dir().join(format!("counit-glossary-{}", uuid::Uuid::new_v4()));
+        // a directory in place of the file of the new records
+        std::fs::create_dir_all(dir.join(NEW_RECORDS_FILE)).unwrap();
+
+        let transpiler = DomainTranspiler::new(&dir);
+        assert!(matches!(transpiler.create(record("本币", "Domestic Currency", "DCY")), Err(GlossaryError::Persistence { .. })));
+        assert!(transpiler.records().is_empty());
+        // the temporary file of the records is removed
+        assert!(!dir.join(format!(".{}.tmp", NEW_RECORDS_FILE)).exists());
+        assert_eq!(transpiler.transpile("本币"), "本币");
+
+        std::fs::remove_dir(dir.join(NEW_RECORDS_FILE)).unwrap();
+        transpiler.create(record("本币", "Domestic Currency", "DCY")).unwrap();
+        assert_eq!(transpiler.records().len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuse_to_write_files_with_skipped_records() {
+        let dir = std::env::temp_dir().join(format!("counit-glossary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let content = "native,english,abbreviation,description\n本币,Domestic Currency,DCY,\n外币,,FCY,\n";
+        std::fs::write(dir.join("currency.csv"), content).unwrap();
+
+        let transpiler = DomainTranspiler::new(&dir);
+        let result = transpiler.update("本币", record("本币", "Local Currency", "LCY"));
+        assert!(matches!(result, Err(GlossaryError::SkippedRecords(_))));
+        assert!(matches!(transpiler.delete("本币"), Err(GlossaryError::SkippedRecords(_))));
+        assert_eq!(std::fs::read_to_string(dir.join("currency.csv")).unwrap(), content);
+        assert_eq!(transpiler.get("本币").unwrap().english, "Domestic Currency");
+
+        // the new records go to another file
+        transpiler.create(record("收单", "Acquiring", "-")).unwrap();
+        assert_eq!(transpiler.records().len(), 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reload_keeps_previous_glossary_on_error() {
+        let dir = std::env::temp_dir().join(format!("counit-glossary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("glossary.csv"), "native,english,abbreviation,description\n本币,Domestic Currency,DCY,\n").unwrap();
+
+        let transpiler = DomainTranspiler::new(&dir);
+        std::fs::write(dir.join("payment.json"), r#"[{"native": "收单", "english": "Acquiring", "abbreviation": "-", "description": ""}]"#).unwrap();
+        assert_eq!(transpiler.reload().unwrap().records, 2);
+        assert_eq!(transpiler.transpile("收单"), "收单(Acquiring)");
+
+        std::fs::write(dir.join("payment.json"), "[{").unwrap();
+        let report = transpiler.reload().unwrap();
+        assert_eq!(transpiler.records().len(), 2);
+        assert_eq!(report.records, 2);
+        let file = report.files.iter().find(|it| it.path.ends_with("payment.json")).unwrap();
+        assert!(!file.loaded);
+        assert_eq!(file.diagnostics.last().unwrap().message, "kept the 1 records loaded before");
+        assert_eq!(transpiler.report(), report);
+
+        assert!(DomainTranspiler::empty().reload().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
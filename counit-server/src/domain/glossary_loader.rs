@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::domain::domain_record::DomainRecord;
+
+/// What was loaded from the domain language directory, and what was skipped.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LoadReport {
+    /// the records loaded from every file
+    pub records: usize,
+    pub files: Vec<FileReport>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub records: usize,
+    /// false when the file can't be read or parsed at all
+    pub loaded: bool,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A record which was skipped or fixed, or an error of the whole file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// the line in a CSV file or a JSON file, none for the whole file
+    pub line: Option<u64>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// the record was fixed and loaded, like its fields were trimmed
+    Info,
+    /// the record was skipped
+    Warning,
+    /// the file can't be read or parsed at all
+    Error,
+}
+
+impl LoadReport {
+    pub fn diagnostic_count(&self) -> usize {
+        self.files.iter().map(|it| it.diagnostics.len()).sum()
+    }
+
+    /// The records which were skipped, in the files which were loaded.
+    pub fn skipped_records(&self) -> usize {
+        self.files.iter()
+            .flat_map(|it| &it.diagnostics)
+            .filter(|it| it.severity == Severity::Warning)
+            .count()
+    }
+
+    pub fn failed_files(&self) -> usize {
+        self.files.iter().filter(|it| !it.loaded).count()
+    }
+
+    /// Whether some records of the files are missing from the glossary, the fixed ones are not.
+    pub fn is_degraded(&self) -> bool {
+        self.skipped_records() > 0 || self.failed_files() > 0
+    }
+}
+
+impl FileReport {
+    fn new(path: &Path) -> Self {
+        FileReport { path: path.to_path_buf(), records: 0, loaded: true, diagnostics: vec![] }
+    }
+
+    /// Whether a record of the file was skipped, or the file can't be loaded.
+    pub fn has_problems(&self) -> bool {
+        !self.loaded || self.diagnostics.iter().any(|it| it.severity != Severity::Info)
+    }
+
+    fn diagnostic(&mut self, line: Option<u64>, severity: Severity, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic { line, severity, message: message.into() });
+    }
+
+    fn fixed(&mut self, line: Option<u64>, message: impl Into<String>) {
+        self.diagnostic(line, Severity::Info, message);
+    }
+
+    fn skipped(&mut self, line: Option<u64>, message: impl Into<String>) {
+        self.diagnostic(line, Severity::Warning, message);
+    }
+
+    fn failed(&mut self, line: Option<u64>, message: impl Into<String>) {
+        self.loaded = false;
+        self.diagnostic(line, Severity::Error, message);
+    }
+}
+
+/// Load the records of the CSV and JSON files of the directory, with the file of each one. The
+/// records which can't be read, have no native word or English name, or repeat the native word
+/// of another one are skipped, and reported with the files which can't be read.
+pub fn load(dir: &Path) -> (Vec<(DomainRecord, PathBuf)>, LoadReport) {
+    use walkdir::WalkDir;
+    let mut records = vec![];
+    let mut report = LoadReport::default();
+    if !dir.exists() {
+        return (records, report);
+    }
+
+    let mut natives = HashSet::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                let path = err.path().unwrap_or(dir).to_path_buf();
+                let mut file = FileReport::new(&path);
+                file.failed(None, err.to_string());
+                report.files.push(file);
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        let mut file = FileReport::new(path);
+        let loaded = match path.extension().and_then(|it| it.to_str()) {
+            Some("csv") => load_csv(path, &mut file),
+            Some("json") => load_json(path, &mut file),
+            _ => continue,
+        };
+
+        for (line, mut record) in loaded {
+            if record.normalize() {
+                file.fixed(line, "trimmed the padding of the fields");
+            }
+            if record.native.is_empty() || record.english.is_empty() {
+                file.skipped(line, "skipped a record without `native` or `english`");
+                continue;
+            }
+            if !natives.insert(record.native.clone()) {
+                file.skipped(line, format!("skipped the duplicate term {}", record.native));
+                continue;
+            }
+
+            file.records += 1;
+            records.push((record, path.to_path_buf()));
+        }
+
+        file.diagnostics.sort_by_key(|it| it.line);
+        for diagnostic in &file.diagnostics {
+            match diagnostic.severity {
+                Severity::Info => info!(?path, line = diagnostic.line, "glossary: {}", diagnostic.message),
+                _ => warn!(?path, line = diagnostic.line, "glossary: {}", diagnostic.message),
+            }
+        }
+        report.records += file.records;
+        report.files.push(file);
+    }
+
+    info!(?dir, records = report.records, skipped = report.skipped_records(), failed_files = report.failed_files(), "glossary loaded");
+    (records, report)
+}
+
+/// The records of the CSV file and their lines, a row which can't be read is reported and
+/// skipped. The rows may leave out the last fields, like the description.
+fn load_csv(path: &Path, file: &mut FileReport) -> Vec<(Option<u64>, DomainRecord)> {
+    let mut reader = match csv::ReaderBuilder::new().trim(csv::Trim::All).flexible(true).from_path(path) {
+        Ok(reader) => reader,
+        Err(err) => {
+            file.failed(None, err.to_string());
+            return vec![];
+        }
+    };
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => {
+            file.failed(err.position().map(|it| it.line()), err.to_string());
+            return vec![];
+        }
+    };
+
+    let mut records = vec![];
+    let mut row = csv::StringRecord::new();
+    loop {
+        match reader.read_record(&mut row) {
+            Ok(false) => break,
+            Ok(true) => {
+                let line = row.position().map(|it| it.line());
+                // the fields left out at the end of the row are empty
+                while row.len() < headers.len() {
+                    row.push_field("");
+                }
+                match row.deserialize::<DomainRecord>(Some(&headers)) {
+                    Ok(record) => records.push((line, record)),
+                    Err(err) => file.skipped(line, format!("skipped a record: {}", err)),
+                }
+            }
+            // the reader can't go on after an io error
+            Err(err) if err.is_io_error() => {
+                file.failed(None, err.to_string());
+                break;
+            }
+            Err(err) => {
+                let line = err.position().map(|it| it.line());
+                file.skipped(line, format!("skipped a record: {}", err));
+            }
+        }
+    }
+
+    records
+}
+
+/// The records of the JSON array of the file, the lines are not known for its items.
+fn load_json(path: &Path, file: &mut FileReport) -> Vec<(Option<u64>, DomainRecord)> {
+    let items = std::fs::read_to_string(path)
+        .map_err(|err| (None, err.to_string()))
+        .and_then(|content| {
+            serde_json::from_str::<Vec<serde_json::Value>>(&content)
+                .map_err(|err| (Some(err.line() as u64), err.to_string()))
+        });
+    let items = match items {
+        Ok(items) => items,
+        Err((line, message)) => {
+            file.failed(line, message);
+            return vec![];
+        }
+    };
+
+    items.into_iter()
+        .enumerate()
+        .filter_map(|(index, item)| match serde_json::from_value::<DomainRecord>(item) {
+            Ok(record) => Some((None, record)),
+            Err(err) => {
+                file.skipped(None, format!("skipped the record {}: {}", index + 1, err));
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_and_report_bad_records() {
+        let dir = std::env::temp_dir().join(format!("counit-glossary-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.csv"), "native,english,abbreviation,description\n\
+            | 本币,Domestic Currency |,DCY,\n\
+            外币,,FCY,\n\
+            汇率,Exchange Rate\n\
+            本币,Local Currency,LCY,\n").unwrap();
+        std::fs::write(dir.join("b.json"), r#"[{"native": "收单", "english": "Acquiring"}, {"native": 1}]"#).unwrap();
+        std::fs::write(dir.join("c.json"), "[{").unwrap();
+
+        let (records, report) = load(&dir);
+        let natives: Vec<&str> = records.iter().map(|(it, _)| it.native.as_str()).collect();
+        assert_eq!(natives, vec!["本币", "汇率", "收单"]);
+        assert_eq!(records[0].0.english, "Domestic Currency");
+        assert_eq!(report.records, 3);
+
+        let lines: Vec<Option<u64>> = report.files[0].diagnostics.iter().map(|it| it.line).collect();
+        assert_eq!(lines, vec![Some(2), Some(3), Some(5)]);
+        assert_eq!(report.files[0].diagnostics[2].message, "skipped the duplicate term 本币");
+        assert!(report.files[1].loaded);
+        assert!(report.files[1].diagnostics[0].message.starts_with("skipped the record 2"));
+        assert!(!report.files[2].loaded);
+        assert_eq!(report.files[2].diagnostics[0].line, Some(1));
+        assert_eq!(report.diagnostic_count(), 5);
+        assert_eq!(report.files[0].diagnostics[0].severity, Severity::Info);
+        assert_eq!((report.skipped_records(), report.failed_files()), (3, 1));
+        assert!(report.is_degraded());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tracing::{info, warn};
+
+use crate::domain::domain_transpiler::DomainTranspiler;
+
+/// How long to wait for more changes before reloading, an editor may write a file several times
+/// when it is saved.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reload the glossary when the CSV or JSON files of its directory change, in a thread which
+/// runs as long as the server.
+pub fn watch(transpiler: Arc<DomainTranspiler>) -> Result<()> {
+    let Some(dir) = transpiler.dir().map(Path::to_path_buf) else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let (event_tx, event_rx) = channel();
+    let mut watcher = notify::recommended_watcher(event_tx)?;
+    watcher.watch(&dir, RecursiveMode::Recursive)
+        .with_context(|| format!("failed to watch {}", dir.display()))?;
+
+    info!(?dir, "watching the glossary");
+    std::thread::Builder::new()
+        .name("glossary-watcher".to_string())
+        .spawn(move || {
+            // the watcher stops when it is dropped
+            let _watcher = watcher;
+            while wait_for_change(&event_rx) {
+                match transpiler.reload() {
+                    Ok(report) => info!(?dir, records = report.records, diagnostics = report.diagnostic_count(), "glossary reloaded"),
+                    Err(err) => warn!(?dir, "failed to reload the glossary, keeping the previous one: {:#}", err),
+                }
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Wait until a glossary file changes and no other change follows for [DEBOUNCE], returns false
+/// when the watcher is stopped.
+fn wait_for_change(events: &Receiver<notify::Result<Event>>) -> bool {
+    loop {
+        let Ok(event) = events.recv() else {
+            return false;
+        };
+        if !is_glossary_change(&event) {
+            continue;
+        }
+
+        while events.recv_timeout(DEBOUNCE).is_ok() {}
+        return true;
+    }
+}
+
+fn is_glossary_change(event: &notify::Result<Event>) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+        && event.paths.iter().any(|path| {
+            matches!(path.extension().and_then(|it| it.to_str()), Some("csv") | Some("json"))
+        })
+}
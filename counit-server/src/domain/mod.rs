@@ -1,2 +1,5 @@
 pub mod domain_record;
 pub mod domain_transpiler;
+pub mod glossary_loader;
+pub mod glossary_watcher;
+pub mod term_matcher;
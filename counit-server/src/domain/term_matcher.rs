@@ -0,0 +1,119 @@
+use std::cmp::Reverse;
+
+use aho_corasick::AhoCorasick;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::domain::domain_record::DomainRecord;
+
+/// Which field of the record a term is.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TermKind {
+    Native,
+    Abbreviation,
+    Description,
+}
+
+/// A term of the glossary found in a text, `start` and `end` are byte offsets in the text.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MatchedTerm {
+    pub term: String,
+    pub english: String,
+    pub kind: TermKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+struct TermPattern {
+    english: String,
+    kind: TermKind,
+}
+
+/// Finds the terms of the glossary in a text in a single pass, with an Aho-Corasick automaton
+/// of the native words, abbreviations and descriptions of the records.
+#[derive(Default)]
+pub struct TermMatcher {
+    automaton: Option<AhoCorasick>,
+    patterns: Vec<TermPattern>,
+}
+
+impl TermMatcher {
+    pub fn new(records: &[DomainRecord]) -> Self {
+        let mut terms = vec![];
+        let mut patterns = vec![];
+        for record in records {
+            let fields = [
+                (&record.native, TermKind::Native),
+                (&record.abbreviation, TermKind::Abbreviation),
+                (&record.description, TermKind::Description),
+            ];
+            for (term, kind) in fields {
+                // `-` is used in the glossaries for no abbreviation
+                let term = term.trim();
+                if term.is_empty() || term == "-" {
+                    continue;
+                }
+
+                terms.push(term.to_string());
+                patterns.push(TermPattern { english: record.english.clone(), kind });
+            }
+        }
+
+        if terms.is_empty() {
+            return TermMatcher::default();
+        }
+
+        match AhoCorasick::new(&terms) {
+            Ok(automaton) => TermMatcher { automaton: Some(automaton), patterns },
+            Err(err) => {
+                warn!(%err, "failed to build the glossary matcher, no term will be matched");
+                TermMatcher::default()
+            }
+        }
+    }
+
+    /// The terms of the text, in order. Of overlapping terms, the leftmost, then the longest one
+    /// is kept, so that `资产配置` isn't matched as `资产`. Latin terms only match whole words,
+    /// so that `CP` doesn't match in `CPU`.
+    pub fn find(&self, text: &str) -> Vec<MatchedTerm> {
+        let Some(automaton) = &self.automaton else {
+            return vec![];
+        };
+
+        let mut candidates: Vec<_> = automaton.find_overlapping_iter(text)
+            .filter(|it| is_whole_word(text, it.start(), it.end()))
+            .collect();
+        candidates.sort_by_key(|it| (it.start(), Reverse(it.end()), it.pattern()));
+
+        let mut terms: Vec<MatchedTerm> = vec![];
+        for candidate in candidates {
+            if terms.last().is_some_and(|it| candidate.start() < it.end) {
+                continue;
+            }
+
+            let pattern = &self.patterns[candidate.pattern().as_usize()];
+            terms.push(MatchedTerm {
+                term: text[candidate.start()..candidate.end()].to_string(),
+                english: pattern.english.clone(),
+                kind: pattern.kind,
+                start: candidate.start(),
+                end: candidate.end(),
+            });
+        }
+
+        terms
+    }
+}
+
+/// A term starting or ending with a Latin letter or digit must not be next to another one, other
+/// terms, like the Chinese ones, have no word boundaries.
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |it: char| it.is_ascii_alphanumeric() || it == '_';
+    let term = &text[start..end];
+
+    let starts_word = term.chars().next().is_some_and(is_word);
+    let ends_word = term.chars().next_back().is_some_and(is_word);
+    (!starts_word || !text[..start].chars().next_back().is_some_and(is_word))
+        && (!ends_word || !text[end..].chars().next().is_some_and(is_word))
+}
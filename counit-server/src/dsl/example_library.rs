@@ -0,0 +1,285 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::dsl::query_description::QAExample;
+use crate::repository::semantic::Embedding;
+
+/// The number of examples in the explain prompt.
+pub const PROMPT_EXAMPLE_COUNT: usize = 3;
+
+/// Embeds the questions to select the examples, like [crate::repository::semantic::Semantic::embed].
+pub type Embedder<'a> = &'a dyn Fn(&str) -> Result<Embedding>;
+
+/// An example of the library, the id is the name of its file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExampleRecord {
+    pub id: String,
+    #[serde(flatten)]
+    pub example: QAExample,
+}
+
+/// The few-shot examples of the explain prompt, each one is kept as `<id>.json` in the directory,
+/// or only in memory if there is no directory. A new directory starts with the built-in examples,
+/// so that they can be tuned to the APIs of the organization.
+pub struct ExampleLibrary {
+    dir: Option<PathBuf>,
+    examples: RwLock<BTreeMap<String, QAExample>>,
+    /// The embeddings of the questions, computed when examples are selected
+    embeddings: RwLock<HashMap<String, Embedding>>,
+}
+
+#[derive(Error, Debug)]
+pub enum ExampleError {
+    #[error("invalid example id `{0}`, use letters, digits, `-` and `_`")]
+    InvalidId(String),
+
+    #[error("failed to save the example: {error}")]
+    Persistence {
+        #[from]
+        error: anyhow::Error,
+    },
+}
+
+impl From<serde_json::Error> for ExampleError {
+    fn from(error: serde_json::Error) -> Self {
+        ExampleError::Persistence { error: error.into() }
+    }
+}
+
+impl Default for ExampleLibrary {
+    fn default() -> Self {
+        ExampleLibrary {
+            dir: None,
+            examples: RwLock::new(builtin_examples()),
+            embeddings: Default::default(),
+        }
+    }
+}
+
+impl ExampleLibrary {
+    /// Load the examples of the directory, files which can't be read are skipped. The built-in
+    /// examples are written to the directory when it is created, deleting them all is kept.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let created = !dir.exists();
+        std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+        let mut examples = BTreeMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|it| it.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|it| it.to_str()).map(|it| it.to_string()) else {
+                continue;
+            };
+
+            let example = std::fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|it| Ok(serde_json::from_str::<QAExample>(&it)?));
+            match example {
+                Ok(example) => {
+                    examples.insert(id, example);
+                }
+                Err(err) => warn!(?path, %err, "skipping invalid explain example"),
+            }
+        }
+
+        let library = ExampleLibrary {
+            dir: Some(dir.to_path_buf()),
+            examples: RwLock::new(BTreeMap::new()),
+            embeddings: Default::default(),
+        };
+
+        if created {
+            for (id, example) in builtin_examples() {
+                library.save(&id, example)?;
+            }
+        } else {
+            *library.examples.write().unwrap() = examples;
+        }
+
+        Ok(library)
+    }
+
+    /// The examples, of the domain if given, ordered by id.
+    pub fn list(&self, domain: Option<&str>) -> Vec<ExampleRecord> {
+        self.examples.read().unwrap()
+            .iter()
+            .filter(|(_, example)| domain.is_none() || domain == Some(example.answer.domain.as_str()))
+            .map(|(id, example)| ExampleRecord { id: id.clone(), example: example.clone() })
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<ExampleRecord> {
+        self.examples.read().unwrap()
+            .get(id)
+            .map(|example| ExampleRecord { id: id.to_string(), example: example.clone() })
+    }
+
+    /// Create or replace the example.
+    pub fn save(&self, id: &str, example: QAExample) -> Result<ExampleRecord, ExampleError> {
+        if !is_valid_id(id) {
+            return Err(ExampleError::InvalidId(id.to_string()));
+        }
+
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", id));
+            let content = serde_json::to_string_pretty(&example)?;
+            std::fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))?;
+        }
+
+        self.embeddings.write().unwrap().remove(id);
+        self.examples.write().unwrap().insert(id.to_string(), example.clone());
+        Ok(ExampleRecord { id: id.to_string(), example })
+    }
+
+    /// Delete the example, returns whether it existed.
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        let removed = self.examples.write().unwrap().remove(id).is_some();
+        self.embeddings.write().unwrap().remove(id);
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", id));
+            if is_valid_id(id) && path.exists() {
+                std::fs::remove_file(&path).with_context(|| format!("failed to delete {}", path.display()))?;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// The examples of the prompt for the question: the most similar ones with the embeddings,
+    /// or the first ones without. They are taken from the domain if given and it has examples.
+    pub fn select(
+        &self,
+        question: &str,
+        domain: Option<&str>,
+        count: usize,
+        embed: Option<Embedder>,
+    ) -> Result<Vec<QAExample>> {
+        let mut examples = self.list(domain);
+        if examples.is_empty() {
+            examples = self.list(None);
+        }
+        let Some(embed) = embed else {
+            return Ok(examples.into_iter().take(count).map(|it| it.example).collect());
+        };
+
+        let query = embed(question)?;
+        let mut scored = Vec::with_capacity(examples.len());
+        for record in examples {
+            let cached = self.embeddings.read().unwrap().get(&record.id).cloned();
+            let embedding = match cached {
+                Some(embedding) => embedding,
+                None => {
+                    let embedding = embed(&record.example.question)?;
+                    self.embeddings.write().unwrap().insert(record.id.clone(), embedding.clone());
+                    embedding
+                }
+            };
+            scored.push((cosine_similarity(&query, &embedding), record.example));
+        }
+
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(count).map(|(_, example)| example).collect())
+    }
+}
+
+fn builtin_examples() -> BTreeMap<String, QAExample> {
+    QAExample::examples().into_iter()
+        .enumerate()
+        .map(|(index, example)| (format!("builtin-{}", index + 1), example))
+        .collect()
+}
+
+/// Ids are file names, so they can't contain paths.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|it| it.is_alphanumeric() || it == '-' || it == '_')
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |it: &[f32]| it.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dsl::example_library::{ExampleError, ExampleLibrary};
+    use crate::dsl::query_description::{ExplainQuery, QAExample};
+    use crate::repository::semantic::Embedding;
+
+    fn example(question: &str, domain: &str) -> QAExample {
+        QAExample {
+            question: question.to_string(),
+            answer: ExplainQuery {
+                domain: domain.to_string(),
+                query: format!("{}: {}", domain, question),
+                nature_lang_query: question.to_string(),
+                hypothetical_document: "GET /api".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn should_persist_examples_in_directory() {
+        let dir = std::env::temp_dir().join(format!("counit-examples-{}", uuid::Uuid::new_v4()));
+
+        let library = ExampleLibrary::load(&dir).unwrap();
+        assert_eq!(library.list(None).len(), 3);
+        assert_eq!(library.list(Some("fund")).len(), 1);
+
+        library.save("refund", example("how to refund", "payment")).unwrap();
+        assert!(matches!(library.save("../refund", example("how to refund", "payment")), Err(ExampleError::InvalidId(_))));
+        assert!(library.delete("builtin-1").unwrap());
+        std::fs::write(dir.join("broken.json"), "{").unwrap();
+
+        let reloaded = ExampleLibrary::load(&dir).unwrap();
+        let ids: Vec<String> = reloaded.list(None).into_iter().map(|it| it.id).collect();
+        assert_eq!(ids, vec!["builtin-2", "builtin-3", "refund"]);
+        assert_eq!(reloaded.get("refund").unwrap().example.question, "how to refund");
+        assert!(!reloaded.delete("builtin-1").unwrap());
+
+        // the built-in examples are only written to a new directory
+        for id in ["builtin-2", "builtin-3", "refund"] {
+            reloaded.delete(id).unwrap();
+        }
+        assert!(ExampleLibrary::load(&dir).unwrap().list(None).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_select_most_similar_examples() {
+        let library = ExampleLibrary::default();
+        library.save("refund", example("refund a trade", "payment")).unwrap();
+        library.save("user", example("query a user", "customer")).unwrap();
+
+        // a fake embedding, counting the words about payments and users
+        let embed = |text: &str| -> anyhow::Result<Embedding> {
+            let count = |words: &[&str]| words.iter().filter(|it| text.contains(*it)).count() as f32;
+            Ok(vec![count(&["trade", "refund", "收单"]), count(&["user", "query", "职得"]), 0.1])
+        };
+
+        let selected = library.select("refund the trade", None, 2, Some(&embed)).unwrap();
+        assert_eq!(selected[0].question, "refund a trade");
+        assert_eq!(selected[1].question, "帮我接入统一收单交易撤销的接口");
+
+        let selected = library.select("query the user", None, 1, Some(&embed)).unwrap();
+        assert_eq!(selected[0].question, "query a user");
+
+        assert_eq!(library.select("anything", None, 2, None).unwrap().len(), 2);
+
+        // only the examples of the domain, or all of them if it has none
+        let selected = library.select("query the user", Some("payment"), 3, Some(&embed)).unwrap();
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|it| it.answer.domain == "payment"));
+        assert_eq!(library.select("query the user", Some("unknown"), 1, Some(&embed)).unwrap()[0].question, "query a user");
+    }
+}
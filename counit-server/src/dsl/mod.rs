@@ -1 +1,2 @@
-pub mod query_description;
\ No newline at end of file
+pub mod query_description;
+pub mod example_library;
@@ -1,18 +1,68 @@
+use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+use crate::agent::templates::{EXPLAIN_QUERY, PromptTemplates};
+use crate::llm::{ChatMessage, LlmClient};
+use crate::repository::literal::Literal;
+use crate::repository::payload::PayloadType;
+use crate::repository::semantic_query::{SearchVector, SemanticQuery};
+
+/// The fields the model leaves out are empty, and not searched with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
 pub struct ExplainQuery {
     #[serde(alias = "领域")]
-    pub domain: &'static str,
+    pub domain: String,
     #[serde(alias = "查询条件")]
-    pub query: &'static str,
-    #[serde(alias = "自然语言查询条件")]
-    pub nature_lang_query: &'static str,
-    #[serde(alias = "假设性文档")]
-    pub hypothetical_document: &'static str,
+    pub query: String,
+    #[serde(alias = "nature_lang_query", alias = "自然语言查询条件")]
+    pub nature_lang_query: String,
+    #[serde(alias = "hypothetical_document", alias = "假设性文档")]
+    pub hypothetical_document: String,
+}
+
+impl ExplainQuery {
+    /// Parse the answer of the model to [QAExample::prompt], the JSON object may be surrounded
+    /// by text or a markdown code block.
+    pub fn from_answer(answer: &str) -> anyhow::Result<ExplainQuery> {
+        let (Some(start), Some(end)) = (answer.find('{'), answer.rfind('}')) else {
+            anyhow::bail!("no JSON object in the answer: {}", answer);
+        };
+        if end < start {
+            anyhow::bail!("no JSON object in the answer: {}", answer);
+        }
+
+        Ok(serde_json::from_str(&answer[start..=end])?)
+    }
+
+    /// A query for each of the texts to search with, filtered by the domain as a repo, and by
+    /// the payload type if given. Empty texts are skipped.
+    pub fn semantic_queries(&self, payload_type: Option<PayloadType>) -> Vec<(SearchVector, SemanticQuery<'static>)> {
+        let texts = [
+            (SearchVector::Query, &self.query),
+            (SearchVector::NatureLangQuery, &self.nature_lang_query),
+            (SearchVector::HypotheticalDocument, &self.hypothetical_document),
+        ];
+
+        texts.into_iter()
+            .filter(|(_, text)| !text.trim().is_empty())
+            .map(|(vector, text)| {
+                let mut query = SemanticQuery {
+                    target: Some(Literal::Plain(Cow::Owned(text.trim().to_string()))),
+                    ..Default::default()
+                };
+                if !self.domain.trim().is_empty() {
+                    query.repos.insert(Literal::Plain(Cow::Owned(self.domain.trim().to_string())));
+                }
+                if let Some(payload_type) = &payload_type {
+                    query.query_types.insert(Literal::Plain(Cow::Owned(payload_type.to_string())));
+                }
+                (vector, query)
+            })
+            .collect()
+    }
 }
 
 impl Display for ExplainQuery {
@@ -22,74 +72,69 @@ impl Display for ExplainQuery {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct QAExample {
-    pub question: &'static str,
+    pub question: String,
     pub answer: ExplainQuery,
 }
 
 impl QAExample {
-    fn examples() -> Vec<QAExample> {
+    /// The built-in examples, see [crate::dsl::example_library::ExampleLibrary] for the
+    /// configured ones.
+    pub fn examples() -> Vec<QAExample> {
         vec![
             QAExample {
-                question: "帮我接入统一收单交易撤销的接口",
+                question: "帮我接入统一收单交易撤销的接口".to_string(),
                 answer: ExplainQuery {
-                    domain: "payment",
-                    query: "payment: cancel Unified Acquiring Transaction",
-                    nature_lang_query: "接入 统一收单交易撤销 接口",
-                    hypothetical_document: "POST /api/alipay/trade/cancel {\"action\":\"close\",\"gmt_refund_pay\":\"officia nostrud est\",\"out_trade_no\":\"6823789339978248\",\"refund_settlement_id\":\"2018101610032004620239146945\",\"retry_flag\":\"N\",\"trade_no\":\"2013112011001004330000121536\"}",
+                    domain: "payment".to_string(),
+                    query: "payment: cancel Unified Acquiring Transaction".to_string(),
+                    nature_lang_query: "接入 统一收单交易撤销 接口".to_string(),
+                    hypothetical_document: "POST /api/alipay/trade/cancel {\"action\":\"close\",\"gmt_refund_pay\":\"officia nostrud est\",\"out_trade_no\":\"6823789339978248\",\"refund_settlement_id\":\"2018101610032004620239146945\",\"retry_flag\":\"N\",\"trade_no\":\"2013112011001004330000121536\"}".to_string(),
                 },
             },
             QAExample {
-                question: "如何查询职得(jobworth)工作证信息？",
+                question: "如何查询职得(jobworth)工作证信息？".to_string(),
                 answer: ExplainQuery {
-                    domain: "customer",
-                    query: "customer: query 职得(jobworth) work permit information",
-                    nature_lang_query: "查询 职得(jobworth)工作证信息",
-                    hypothetical_document: "GET /api/customer/jobworth/info/query?user_name=张三",
+                    domain: "customer".to_string(),
+                    query: "customer: query 职得(jobworth) work permit information".to_string(),
+                    nature_lang_query: "查询 职得(jobworth)工作证信息".to_string(),
+                    hypothetical_document: "GET /api/customer/jobworth/info/query?user_name=张三".to_string(),
                 },
             },
             QAExample {
-                question: "因公付(enterprisepay)更新员工资金协议",
+                question: "因公付(enterprisepay)更新员工资金协议".to_string(),
                 answer: ExplainQuery {
-                    domain: "fund",
-                    query: "fund: update employee fund agreement for enterprisepay",
-                    nature_lang_query: "更新 因公付(enterprisepay)员工资金协议",
-                    hypothetical_document: "PUT /api/fund/enterprisepay/sign {\"employee_id\": \"12345\", \"agreement_type\": \"fund\", \"update_fields\": {\"bank_account\": \"987654321\", \"amount\": 1500.00}}",
+                    domain: "fund".to_string(),
+                    query: "fund: update employee fund agreement for enterprisepay".to_string(),
+                    nature_lang_query: "更新 因公付(enterprisepay)员工资金协议".to_string(),
+                    hypothetical_document: "PUT /api/fund/enterprisepay/sign {\"employee_id\": \"12345\", \"agreement_type\": \"fund\", \"update_fields\": {\"bank_account\": \"987654321\", \"amount\": 1500.00}}".to_string(),
                 },
             },
         ]
     }
 
-    pub fn prompt(query: &str) -> String {
-        let mut prompt = String::new();
-        prompt += r#"Your job is to transpile user's question relative to codebase.
-
-1. YOU MUST follow the DSL format.
-2. You MUST translate user's question into a DSL query.
-3. `query` is a reference to the document that you think is the answer to the question.
-4. `hypothetical_document` is a example of the document that you think is the answer to the question.
-
-For example:
-
-"#;
-
-        for example in Self::examples() {
-            prompt.push_str(&format!("Q: {}\n", example.question));
-            prompt.push_str(&format!("A: {}\n", example.answer));
-            prompt.push_str(&format!("\n"));
-        }
+    pub fn prompt(templates: &PromptTemplates, examples: &[QAExample], query: &str) -> String {
+        let examples: String = examples.iter()
+            .map(|example| format!("Q: {}\nA: {}\n\n", example.question, example.answer))
+            .collect();
 
-        prompt.push_str(&format!("Q: {}\n", query));
-        prompt.push_str(&format!("A:"));
+        templates.render(EXPLAIN_QUERY, &[("examples", &examples), ("query", query)])
+    }
 
-        prompt
+    /// Ask the model to translate the question into an [ExplainQuery], its JSON answer is
+    /// returned as is.
+    pub async fn explain(llm: &dyn LlmClient, templates: &PromptTemplates, examples: &[QAExample], query: &str) -> anyhow::Result<String> {
+        let answer = llm.chat(&[ChatMessage::user(&Self::prompt(templates, examples, query))]).await?;
+        Ok(answer.trim().to_string())
     }
 }
 
 // test
 #[cfg(test)]
 mod tests {
+    use crate::agent::templates::PromptTemplates;
+    use crate::llm::scripted_llm::ScriptedLlm;
+
     use super::*;
 
     #[test]
@@ -116,9 +161,47 @@ mod tests {
         assert_eq!(config.domain, "API");
     }
 
+    #[test]
+    fn should_parse_answer_of_model() {
+        let answer = "```json\n{\"domain\": \"payment\", \"query\": \"payment: cancel trade\", \"natureLangQuery\": \"撤销 交易\", \"hypotheticalDocument\": \"POST /api/trade/cancel\"}\n```";
+        let explain = ExplainQuery::from_answer(answer).unwrap();
+        assert_eq!(explain.hypothetical_document, "POST /api/trade/cancel");
+        assert!(ExplainQuery::from_answer("} no json {").is_err());
+
+        let explain = ExplainQuery::from_answer(r#"{"domain": "payment", "query": "payment: cancel trade"}"#).unwrap();
+        assert_eq!(explain.hypothetical_document, "");
+        assert_eq!(explain.semantic_queries(None).len(), 1);
+    }
+
+    #[test]
+    fn should_build_a_query_for_each_text() {
+        let explain = ExplainQuery {
+            domain: "payment".to_string(),
+            query: "payment: cancel trade".to_string(),
+            nature_lang_query: " ".to_string(),
+            hypothetical_document: "POST /api/trade/cancel".to_string(),
+        };
+
+        let queries = explain.semantic_queries(Some(PayloadType::HttpApi));
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[1].0, SearchVector::HypotheticalDocument);
+        assert_eq!(queries[1].1.target().unwrap(), "POST /api/trade/cancel");
+        assert_eq!(queries[1].1.repos().collect::<Vec<_>>(), vec!["payment"]);
+        assert_eq!(queries[1].1.query_types().collect::<Vec<_>>(), vec!["http_api"]);
+    }
+
+    #[tokio::test]
+    async fn should_explain_with_llm() {
+        let llm = ScriptedLlm::new(vec![" {\"domain\": \"payment\"}\n"]);
+        let answer = QAExample::explain(&llm, &PromptTemplates::default(), &QAExample::examples(), "如何撤销交易").await.unwrap();
+
+        assert_eq!(answer, "{\"domain\": \"payment\"}");
+        assert!(llm.requests()[0][0].content.ends_with("Q: 如何撤销交易\nA:"));
+    }
+
     #[test]
     fn prompt_sample() {
-        let prompt = QAExample::prompt("帮我接入统一收单交易撤销的接口");
+        let prompt = QAExample::prompt(&PromptTemplates::default(), &QAExample::examples(), "帮我接入统一收单交易撤销的接口");
         println!("{}", prompt);
     }
 }
\ No newline at end of file
@@ -0,0 +1,320 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use crate::graph::graph_store::{DatamapEntry, ServiceEntry};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Service,
+    /// a url that is called, but not provided by any known service
+    External,
+    Function,
+    Table,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GraphNode {
+    pub id: String,
+    pub label: String,
+    pub kind: NodeKind,
+    /// the repo which reported this node, empty for nodes only known as a call target
+    pub repo_id: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub label: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Graph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+impl Graph {
+    fn add_node(&mut self, id: &str, label: &str, kind: NodeKind, repo_id: &str) {
+        if self.nodes.iter().any(|it| it.id == id) {
+            return;
+        }
+
+        self.nodes.push(GraphNode {
+            id: id.to_string(),
+            label: label.to_string(),
+            kind,
+            repo_id: repo_id.to_string(),
+        });
+    }
+
+    fn add_edge(&mut self, source: &str, target: &str, label: &str) {
+        let edge = GraphEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            label: label.to_string(),
+        };
+
+        if !self.edges.contains(&edge) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Build the service call graph: an edge goes from the service with a demand to the service
+    /// whose resource matches the demanded url and http method.
+    pub fn from_services(entries: &[ServiceEntry]) -> Graph {
+        let mut graph = Graph::default();
+
+        for entry in entries {
+            let name = entry.service_name();
+            graph.add_node(&format!("service:{}", name), &name, NodeKind::Service, &entry.repo_id);
+        }
+
+        for entry in entries {
+            let source = format!("service:{}", entry.service_name());
+            for demand in &entry.service.demands {
+                let label = format!("{} {}", demand.target_http_method.to_uppercase(), demand.target_url)
+                    .trim()
+                    .to_string();
+
+                let providers = entries
+                    .iter()
+                    .filter(|provider| {
+                        provider.service.resources.iter().any(|resource| {
+                            is_same_method(&demand.target_http_method, &resource.source_http_method)
+                                && normalize_url(&demand.target_url) == normalize_url(&resource.source_url)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                if providers.is_empty() {
+                    let target = format!("external:{}", demand.target_url);
+                    graph.add_node(&target, &demand.target_url, NodeKind::External, "");
+                    graph.add_edge(&source, &target, &label);
+                }
+
+                for provider in providers {
+                    graph.add_edge(&source, &format!("service:{}", provider.service_name()), &label);
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// Build the data map graph: functions point to the tables they use, and `relations` are
+    /// added as they were reported, e.g. a controller calling a repository function.
+    pub fn from_datamaps(entries: &[DatamapEntry]) -> Graph {
+        let mut graph = Graph::default();
+
+        let tables: HashSet<&str> = entries
+            .iter()
+            .flat_map(|it| it.relation.tables.iter().map(|table| table.as_str()))
+            .collect();
+        let node_id = |name: &str| -> (String, NodeKind) {
+            if tables.contains(name) {
+                (format!("table:{}", name), NodeKind::Table)
+            } else {
+                (format!("function:{}", name), NodeKind::Function)
+            }
+        };
+
+        for entry in entries {
+            let function = entry.relation.canonical_name();
+            let source = format!("function:{}", function);
+            graph.add_node(&source, &function, NodeKind::Function, &entry.repo_id);
+
+            for table in &entry.relation.tables {
+                let target = format!("table:{}", table);
+                graph.add_node(&target, table, NodeKind::Table, "");
+                graph.add_edge(&source, &target, "");
+            }
+
+            for relation in &entry.relation.relations {
+                let (source, source_kind) = node_id(&relation.source);
+                let (target, target_kind) = node_id(&relation.target);
+                graph.add_node(&source, &relation.source, source_kind, &entry.repo_id);
+                graph.add_node(&target, &relation.target, target_kind, "");
+                graph.add_edge(&source, &target, "");
+            }
+        }
+
+        graph
+    }
+
+    /// Keep only the nodes reported by `repo_id` and the nodes reachable from them within `depth`
+    /// hops, following edges in both directions so that callers and callees both show up.
+    ///
+    /// With no `repo_id` the whole graph is returned, with no `depth` there is no hop limit.
+    pub fn neighbourhood(self, repo_id: Option<&str>, depth: Option<usize>) -> Graph {
+        let Some(repo_id) = repo_id else {
+            return self;
+        };
+
+        let mut adjacent: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.edges {
+            adjacent.entry(edge.source.as_str()).or_default().push(edge.target.as_str());
+            adjacent.entry(edge.target.as_str()).or_default().push(edge.source.as_str());
+        }
+
+        let mut visited: HashMap<&str, usize> = HashMap::new();
+        let mut queue: VecDeque<&str> = VecDeque::new();
+        for node in self.nodes.iter().filter(|it| it.repo_id == repo_id) {
+            visited.insert(node.id.as_str(), 0);
+            queue.push_back(node.id.as_str());
+        }
+
+        while let Some(id) = queue.pop_front() {
+            let hops = visited[id];
+            if depth.is_some_and(|depth| hops >= depth) {
+                continue;
+            }
+
+            for next in adjacent.get(id).into_iter().flatten() {
+                if !visited.contains_key(next) {
+                    visited.insert(next, hops + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let keep: HashSet<String> = visited.keys().map(|it| it.to_string()).collect();
+        Graph {
+            nodes: self.nodes.into_iter().filter(|it| keep.contains(&it.id)).collect(),
+            edges: self
+                .edges
+                .into_iter()
+                .filter(|it| keep.contains(&it.source) && keep.contains(&it.target))
+                .collect(),
+        }
+    }
+}
+
+/// An empty method, or `Any` for routes like gin's `r.Any(...)`, matches every method.
+fn is_same_method(demand: &str, resource: &str) -> bool {
+    let is_any = |method: &str| method.is_empty() || method.eq_ignore_ascii_case("any");
+    is_any(demand) || is_any(resource) || demand.eq_ignore_ascii_case(resource)
+}
+
+/// Reduce a url to a comparable path: scheme, host and query are dropped, path variables like
+/// `{id}`, `:id` or `${id}` become `{}`.
+fn normalize_url(url: &str) -> String {
+    let path = match url.find("://") {
+        Some(index) => {
+            let without_scheme = &url[index + 3..];
+            without_scheme.find('/').map_or("", |slash| &without_scheme[slash..])
+        }
+        None => url,
+    };
+    let path = path.split('?').next().unwrap_or_default();
+
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            if segment.starts_with('{') || segment.starts_with(':') || segment.starts_with('$') {
+                "{}".to_string()
+            } else {
+                segment.to_lowercase()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::call_graph::{Graph, NodeKind};
+    use crate::graph::graph_store::GraphStore;
+    use crate::model::{CodeDatabaseRelation, ContainerService};
+
+    fn services() -> Vec<ContainerService> {
+        serde_json::from_str(r#"[
+  {
+    "name": "order",
+    "demands": [
+      { "targetUrl": "http://payment/api/payments/{orderId}", "targetHttpMethod": "Post" },
+      { "targetUrl": "/api/unknown", "targetHttpMethod": "Get" }
+    ],
+    "resources": [
+      { "sourceUrl": "/api/orders", "sourceHttpMethod": "Get", "packageName": "com.order", "className": "OrderController", "methodName": "list" }
+    ]
+  },
+  {
+    "name": "payment",
+    "demands": [
+      { "targetUrl": "/api/accounts/:id", "targetHttpMethod": "Get" }
+    ],
+    "resources": [
+      { "sourceUrl": "/api/payments/{id}", "sourceHttpMethod": "Post", "packageName": "com.payment", "className": "PaymentController", "methodName": "pay" }
+    ]
+  },
+  {
+    "name": "account",
+    "resources": [
+      { "sourceUrl": "/api/accounts/{accountId}", "sourceHttpMethod": "Get", "packageName": "com.account", "className": "AccountController", "methodName": "get" }
+    ]
+  }
+]"#).unwrap()
+    }
+
+    fn store() -> GraphStore {
+        let store = GraphStore::default();
+        let services = services();
+        store.save_services("1", "order", &services[0..1]);
+        store.save_services("1", "payment", &services[1..2]);
+        store.save_services("1", "account", &services[2..3]);
+        store
+    }
+
+    #[test]
+    fn should_link_demand_to_resource() {
+        let graph = Graph::from_services(&store().services(Some("1")));
+
+        assert_eq!(graph.nodes.len(), 4);
+        assert!(graph.edges.iter().any(|it| it.source == "service:order" && it.target == "service:payment"));
+        assert!(graph.edges.iter().any(|it| it.source == "service:payment" && it.target == "service:account"));
+        assert!(graph.nodes.iter().any(|it| it.id == "external:/api/unknown" && it.kind == NodeKind::External));
+    }
+
+    #[test]
+    fn should_limit_graph_by_depth() {
+        let graph = Graph::from_services(&store().services(Some("1")));
+        let graph = graph.neighbourhood(Some("order"), Some(1));
+
+        let ids: Vec<&str> = graph.nodes.iter().map(|it| it.id.as_str()).collect();
+        assert!(ids.contains(&"service:payment"));
+        assert!(!ids.contains(&"service:account"));
+        assert_eq!(graph.edges.len(), 2);
+    }
+
+    #[test]
+    fn should_replace_services_of_same_repo() {
+        let store = store();
+        store.save_services("1", "order", &services()[0..1]);
+
+        assert_eq!(store.services(Some("1")).len(), 3);
+        assert!(store.services(Some("2")).is_empty());
+    }
+
+    #[test]
+    fn should_build_datamap_graph() {
+        let relations: Vec<CodeDatabaseRelation> = serde_json::from_str(r#"[
+  {
+    "packageName": "com.order",
+    "className": "OrderRepository",
+    "functionName": "save",
+    "tables": ["orders"],
+    "relations": [{ "source": "com.order.OrderController.create", "target": "com.order.OrderRepository.save" }]
+  }
+]"#).unwrap();
+        let store = GraphStore::default();
+        store.save_datamaps("1", "order", &relations);
+
+        let graph = Graph::from_datamaps(&store.datamaps(None));
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(graph.edges.iter().any(|it| it.source == "function:com.order.OrderRepository.save" && it.target == "table:orders"));
+        assert!(graph.edges.iter().any(|it| it.source == "function:com.order.OrderController.create"));
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::graph::call_graph::{Graph, NodeKind};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    #[default]
+    Mermaid,
+    PlantUml,
+    Dot,
+}
+
+impl Graph {
+    pub fn render(&self, format: DiagramFormat) -> String {
+        match format {
+            DiagramFormat::Mermaid => self.to_mermaid(),
+            DiagramFormat::PlantUml => self.to_plantuml(),
+            DiagramFormat::Dot => self.to_dot(),
+        }
+    }
+
+    /// Node ids like `function:com.example.Foo.bar` are not valid identifiers in every diagram
+    /// language, so each node is aliased as `n0`, `n1`, ...
+    fn aliases(&self) -> HashMap<&str, String> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (node.id.as_str(), format!("n{}", index)))
+            .collect()
+    }
+
+    pub fn to_mermaid(&self) -> String {
+        let aliases = self.aliases();
+        let mut output = String::from("graph LR\n");
+
+        for node in &self.nodes {
+            let label = node.label.replace('"', "#quot;");
+            let shape = match node.kind {
+                NodeKind::Service => format!("[\"{}\"]", label),
+                NodeKind::External => format!("((\"{}\"))", label),
+                NodeKind::Function => format!("(\"{}\")", label),
+                NodeKind::Table => format!("[(\"{}\")]", label),
+            };
+            output.push_str(&format!("    {}{}\n", aliases[node.id.as_str()], shape));
+        }
+
+        for edge in &self.edges {
+            let source = &aliases[edge.source.as_str()];
+            let target = &aliases[edge.target.as_str()];
+            if edge.label.is_empty() {
+                output.push_str(&format!("    {} --> {}\n", source, target));
+            } else {
+                let label = edge.label.replace('"', "#quot;");
+                output.push_str(&format!("    {} -->|\"{}\"| {}\n", source, label, target));
+            }
+        }
+
+        output
+    }
+
+    pub fn to_plantuml(&self) -> String {
+        let aliases = self.aliases();
+        let mut output = String::from("@startuml\n");
+
+        for node in &self.nodes {
+            let element = match node.kind {
+                NodeKind::Service => "component",
+                NodeKind::External => "cloud",
+                NodeKind::Function => "rectangle",
+                NodeKind::Table => "database",
+            };
+            output.push_str(&format!(
+                "{} \"{}\" as {}\n",
+                element,
+                node.label.replace('"', "'"),
+                aliases[node.id.as_str()]
+            ));
+        }
+
+        for edge in &self.edges {
+            let source = &aliases[edge.source.as_str()];
+            let target = &aliases[edge.target.as_str()];
+            if edge.label.is_empty() {
+                output.push_str(&format!("{} --> {}\n", source, target));
+            } else {
+                output.push_str(&format!("{} --> {} : {}\n", source, target, edge.label));
+            }
+        }
+
+        output.push_str("@enduml\n");
+        output
+    }
+
+    pub fn to_dot(&self) -> String {
+        let aliases = self.aliases();
+        let mut output = String::from("digraph G {\n    rankdir=LR;\n");
+
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Service => "box",
+                NodeKind::External => "ellipse",
+                NodeKind::Function => "box, style=rounded",
+                NodeKind::Table => "cylinder",
+            };
+            output.push_str(&format!(
+                "    {} [label=\"{}\", shape={}];\n",
+                aliases[node.id.as_str()],
+                escape_dot(&node.label),
+                shape
+            ));
+        }
+
+        for edge in &self.edges {
+            let source = &aliases[edge.source.as_str()];
+            let target = &aliases[edge.target.as_str()];
+            if edge.label.is_empty() {
+                output.push_str(&format!("    {} -> {};\n", source, target));
+            } else {
+                output.push_str(&format!("    {} -> {} [label=\"{}\"];\n", source, target, escape_dot(&edge.label)));
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::call_graph::{Graph, GraphEdge, GraphNode, NodeKind};
+    use crate::graph::graph_render::DiagramFormat;
+
+    fn graph() -> Graph {
+        Graph {
+            nodes: vec![
+                GraphNode { id: "service:order".to_string(), label: "order".to_string(), kind: NodeKind::Service, repo_id: "order".to_string() },
+                GraphNode { id: "table:orders".to_string(), label: "orders".to_string(), kind: NodeKind::Table, repo_id: "".to_string() },
+            ],
+            edges: vec![
+                GraphEdge { source: "service:order".to_string(), target: "table:orders".to_string(), label: "GET /api/orders".to_string() },
+            ],
+        }
+    }
+
+    #[test]
+    fn should_render_mermaid() {
+        assert_eq!(graph().render(DiagramFormat::Mermaid), r#"graph LR
+    n0["order"]
+    n1[("orders")]
+    n0 -->|"GET /api/orders"| n1
+"#);
+    }
+
+    #[test]
+    fn should_render_plantuml() {
+        assert_eq!(graph().render(DiagramFormat::PlantUml), r#"@startuml
+component "order" as n0
+database "orders" as n1
+n0 --> n1 : GET /api/orders
+@enduml
+"#);
+    }
+
+    #[test]
+    fn should_render_dot() {
+        assert_eq!(graph().render(DiagramFormat::Dot), r#"digraph G {
+    rankdir=LR;
+    n0 [label="order", shape=box];
+    n1 [label="orders", shape=cylinder];
+    n0 -> n1 [label="GET /api/orders"];
+}
+"#);
+    }
+}
@@ -0,0 +1,96 @@
+use std::sync::RwLock;
+
+use crate::model::{CodeDatabaseRelation, ContainerService};
+
+/// A container service reported by ArchGuard, together with where it came from.
+#[derive(Clone)]
+pub struct ServiceEntry {
+    pub system_id: String,
+    pub repo_id: String,
+    pub service: ContainerService,
+}
+
+impl ServiceEntry {
+    /// ArchGuard leaves the service name empty for most languages, so fall back to the repo id.
+    pub fn service_name(&self) -> String {
+        if self.service.name.is_empty() {
+            self.repo_id.clone()
+        } else {
+            self.service.name.clone()
+        }
+    }
+}
+
+/// A function-to-table data map reported by ArchGuard, together with where it came from.
+#[derive(Clone)]
+pub struct DatamapEntry {
+    pub system_id: String,
+    pub repo_id: String,
+    pub relation: CodeDatabaseRelation,
+}
+
+/// In-memory store of the relations we received, used to draw service and data map diagrams.
+///
+/// Entries are upserted, so uploading the same repo again replaces the previous relations instead
+/// of duplicating them.
+#[derive(Default)]
+pub struct GraphStore {
+    services: RwLock<Vec<ServiceEntry>>,
+    datamaps: RwLock<Vec<DatamapEntry>>,
+}
+
+impl GraphStore {
+    pub fn save_services(&self, system_id: &str, repo_id: &str, services: &[ContainerService]) {
+        let mut entries = self.services.write().unwrap();
+        for service in services {
+            let entry = ServiceEntry {
+                system_id: system_id.to_string(),
+                repo_id: repo_id.to_string(),
+                service: service.clone(),
+            };
+
+            let name = entry.service_name();
+            entries.retain(|it| {
+                !(it.system_id == system_id && it.repo_id == repo_id && it.service_name() == name)
+            });
+            entries.push(entry);
+        }
+    }
+
+    pub fn save_datamaps(&self, system_id: &str, repo_id: &str, relations: &[CodeDatabaseRelation]) {
+        let mut entries = self.datamaps.write().unwrap();
+        for relation in relations {
+            let name = relation.canonical_name();
+            entries.retain(|it| {
+                !(it.system_id == system_id && it.repo_id == repo_id && it.relation.canonical_name() == name)
+            });
+            entries.push(DatamapEntry {
+                system_id: system_id.to_string(),
+                repo_id: repo_id.to_string(),
+                relation: relation.clone(),
+            });
+        }
+    }
+
+    /// All services of a system, or of every system when `system_id` is `None`.
+    pub fn services(&self, system_id: Option<&str>) -> Vec<ServiceEntry> {
+        self.services
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|it| system_id.is_none() || system_id == Some(it.system_id.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// All data maps of a system, or of every system when `system_id` is `None`.
+    pub fn datamaps(&self, system_id: Option<&str>) -> Vec<DatamapEntry> {
+        self.datamaps
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|it| system_id.is_none() || system_id == Some(it.system_id.as_str()))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,3 @@
+pub mod graph_store;
+pub mod call_graph;
+pub mod graph_render;
@@ -0,0 +1,114 @@
+use anyhow::{bail, Result};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::agent::prompts;
+use crate::agent::templates::PromptTemplates;
+
+pub mod model;
+pub mod openai_client;
+pub mod scripted_llm;
+pub mod tokens;
+
+/// A message of a chat completion, `role` is one of `system`, `user` or `assistant`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: &str) -> Self {
+        ChatMessage { role: "system".to_string(), content: content.to_string() }
+    }
+
+    pub fn user(content: &str) -> Self {
+        ChatMessage { role: "user".to_string(), content: content.to_string() }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        ChatMessage { role: "assistant".to_string(), content: content.to_string() }
+    }
+}
+
+/// A tool call of the model with native function calling, the arguments are a JSON object, or
+/// a string of it as returned by OpenAI.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(alias = "input")]
+    pub arguments: Value,
+}
+
+/// A chat model, like [openai_client::OpenAiClient], or [scripted_llm::ScriptedLlm] in tests.
+#[async_trait::async_trait]
+pub trait LlmClient: Send + Sync {
+    /// Stream the completion of the conversation, as the pieces of text the model generates.
+    async fn chat_stream(&self, messages: &[ChatMessage]) -> Result<BoxStream<'static, Result<String>>>;
+
+    /// The whole completion of the conversation.
+    async fn chat(&self, messages: &[ChatMessage]) -> Result<String> {
+        let mut stream = self.chat_stream(messages).await?;
+        let mut completion = String::new();
+        while let Some(delta) = stream.next().await {
+            completion.push_str(&delta?);
+        }
+
+        Ok(completion)
+    }
+
+    /// Let the model call one of the functions, which are defined in the format of the provider,
+    /// see [crate::agent::tools::function_schemas].
+    async fn call_function(&self, _messages: &[ChatMessage], _functions: &Value) -> Result<FunctionCall> {
+        bail!("the model does not support function calling")
+    }
+}
+
+/// Ask the model for a REST api snippet which could answer the query, for HyDE (Hypothetical
+/// Document Embeddings) search: the snippet is closer to the indexed apis than the question.
+pub async fn hypothetical_document(llm: &dyn LlmClient, templates: &PromptTemplates, query: &str) -> Result<String> {
+    let prompt = prompts::hypothetical_document_api_prompt(templates, query);
+    let completion = llm.chat(&[ChatMessage::user(&prompt)]).await?;
+
+    Ok(code_block_of(&completion).unwrap_or(completion.trim()).to_string())
+}
+
+/// The content of the first markdown code block of the text, without its language.
+fn code_block_of(text: &str) -> Option<&str> {
+    let (_, rest) = text.split_once("```")?;
+    let (_language, rest) = rest.split_once('\n')?;
+    let (block, _) = rest.split_once("```")?;
+    Some(block.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::templates::PromptTemplates;
+    use crate::llm::{hypothetical_document, LlmClient};
+    use crate::llm::scripted_llm::ScriptedLlm;
+
+    #[tokio::test]
+    async fn should_extract_hypothetical_document_from_code_block() {
+        let llm = ScriptedLlm::new(vec![
+            "Here is a snippet:\n```http\nGET /api/blogs/{id}\n```\n",
+            "POST /api/blogs",
+        ]);
+
+        let templates = PromptTemplates::default();
+
+        assert_eq!(hypothetical_document(&llm, &templates, "get a blog").await.unwrap(), "GET /api/blogs/{id}");
+        assert_eq!(hypothetical_document(&llm, &templates, "create a blog").await.unwrap(), "POST /api/blogs");
+        assert!(llm.requests()[0][0].content.contains("get a blog"));
+    }
+
+    #[tokio::test]
+    async fn should_collect_streamed_completion() {
+        let llm = ScriptedLlm::new(vec!["the payment is created in PaymentService"]);
+        let completion = llm.chat(&[]).await.unwrap();
+
+        assert_eq!(completion, "the payment is created in PaymentService");
+        assert!(llm.chat(&[]).await.is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::agent::tools::ToolFormat;
+use crate::configuration::Configuration;
+use crate::llm::LlmClient;
+use crate::llm::openai_client::OpenAiClient;
+
+/// The chat model of the `llm_*` settings of the configuration, which can be replaced without a
+/// restart, see [ModelStore].
+#[derive(Clone)]
+pub struct Model {
+    /// Chat completions for the agent, the explain flow and HyDE, disabled without `llm_base_url`
+    pub client: Option<Arc<dyn LlmClient>>,
+    pub name: String,
+    pub tool_format: ToolFormat,
+    pub context_window: usize,
+    pub max_completion_tokens: usize,
+}
+
+/// The settings of the model, without the api key.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModelSummary {
+    pub enabled: bool,
+    pub name: String,
+    pub tool_format: ToolFormat,
+    pub context_window: usize,
+    pub max_completion_tokens: usize,
+}
+
+impl Model {
+    /// The model of the configuration with the given client.
+    pub fn new(config: &Configuration, client: Option<Arc<dyn LlmClient>>) -> Self {
+        Model {
+            client,
+            name: config.llm_model.clone(),
+            tool_format: config.llm_tool_format,
+            context_window: config.llm_context_window,
+            max_completion_tokens: config.llm_max_completion_tokens,
+        }
+    }
+
+    /// The model of the configuration, with an OpenAI compatible client of `llm_base_url`.
+    pub fn from_config(config: &Configuration) -> Result<Self> {
+        // the client of `llm_base_url` speaks the OpenAI api, which can't take Anthropic tools
+        if config.llm_tool_format == ToolFormat::Anthropic {
+            bail!("`llm_tool_format` can't be `anthropic` with the OpenAI compatible client of `llm_base_url`, use `openai` or `text`");
+        }
+
+        let client = config.llm_base_url.as_ref().map(|url| {
+            Arc::new(OpenAiClient::new(
+                url,
+                &config.llm_model,
+                config.llm_api_key.clone(),
+                config.llm_max_completion_tokens,
+            )) as Arc<dyn LlmClient>
+        });
+        Ok(Model::new(config, client))
+    }
+
+    pub fn summary(&self) -> ModelSummary {
+        ModelSummary {
+            enabled: self.client.is_some(),
+            name: self.name.clone(),
+            tool_format: self.tool_format,
+            context_window: self.context_window,
+            max_completion_tokens: self.max_completion_tokens,
+        }
+    }
+}
+
+/// Keeps the current model, the requests take it once so that a reload doesn't change the model
+/// in the middle of a request.
+pub struct ModelStore {
+    current: RwLock<Arc<Model>>,
+}
+
+impl ModelStore {
+    pub fn new(model: Model) -> Self {
+        ModelStore { current: RwLock::new(Arc::new(model)) }
+    }
+
+    pub fn current(&self) -> Arc<Model> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    /// The client of the current model, none if it is disabled.
+    pub fn client(&self) -> Option<Arc<dyn LlmClient>> {
+        self.current().client.clone()
+    }
+
+    pub fn replace(&self, model: Model) {
+        *self.current.write().unwrap() = Arc::new(model);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agent::tools::ToolFormat;
+    use crate::configuration::Configuration;
+    use crate::llm::model::{Model, ModelStore};
+
+    #[test]
+    fn should_replace_the_model_of_the_configuration() {
+        let mut config = Configuration::default();
+        let store = ModelStore::new(Model::from_config(&config).unwrap());
+        assert!(store.client().is_none());
+
+        config.llm_base_url = Some("http://127.0.0.1:11434/v1".to_string());
+        config.llm_model = "qwen2".to_string();
+        let previous = store.current();
+        store.replace(Model::from_config(&config).unwrap());
+
+        assert!(store.client().is_some());
+        assert_eq!(store.current().summary().name, "qwen2");
+        // the model taken before the reload is kept by the request
+        assert!(previous.client.is_none());
+
+        config.llm_tool_format = ToolFormat::Anthropic;
+        assert!(Model::from_config(&config).is_err());
+    }
+}
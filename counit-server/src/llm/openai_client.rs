@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Context};
+use eventsource_stream::Eventsource;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::llm::{ChatMessage, FunctionCall, LlmClient};
+
+/// A client of an OpenAI compatible chat completions api, like OpenAI, Azure OpenAI behind a
+/// gateway, vLLM or Ollama.
+pub struct OpenAiClient {
+    http: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+    /// the tokens of the completion, which are left out of the budget of the prompts
+    max_tokens: usize,
+}
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    temperature: f32,
+    max_tokens: usize,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<ChatChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChatChoice {
+    message: ResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    /// null when the model calls a tool
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    function: FunctionCall,
+}
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    choices: Vec<ChunkChoice>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: ChunkDelta,
+}
+
+#[derive(Deserialize)]
+struct ChunkDelta {
+    /// the first chunk only has the role
+    content: Option<String>,
+}
+
+impl OpenAiClient {
+    pub fn new(base_url: &str, model: &str, api_key: Option<String>, max_tokens: usize) -> Self {
+        OpenAiClient {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            api_key,
+            max_tokens,
+        }
+    }
+
+    async fn send(&self, request: &ChatRequest<'_>) -> anyhow::Result<reqwest::Response> {
+        let mut request = self.http
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(request);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        let response = request.send().await.context("chat completion request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("chat completion failed with {}: {}", status, body));
+        }
+
+        Ok(response)
+    }
+
+    fn request<'a>(&'a self, messages: &'a [ChatMessage], stream: bool) -> ChatRequest<'a> {
+        ChatRequest {
+            model: &self.model,
+            messages,
+            temperature: 0.0,
+            max_tokens: self.max_tokens,
+            stream,
+            tools: None,
+            tool_choice: None,
+        }
+    }
+
+    async fn complete(&self, request: &ChatRequest<'_>) -> anyhow::Result<ResponseMessage> {
+        let response: ChatResponse = self.send(request).await?
+            .json()
+            .await
+            .context("invalid chat completion response")?;
+        response.choices.into_iter()
+            .next()
+            .map(|choice| choice.message)
+            .ok_or(anyhow!("chat completion returned no choice"))
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for OpenAiClient {
+    /// The completion streamed as server-sent events, until the `[DONE]` event.
+    async fn chat_stream(&self, messages: &[ChatMessage]) -> anyhow::Result<BoxStream<'static, anyhow::Result<String>>> {
+        let response = self.send(&self.request(messages, true)).await?;
+
+        let deltas = response.bytes_stream()
+            .eventsource()
+            .take_while(|event| {
+                let done = matches!(event, Ok(event) if event.data == "[DONE]");
+                futures::future::ready(!done)
+            })
+            .filter_map(|event| async move {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => return Some(Err(anyhow!("invalid chat completion stream: {}", err))),
+                };
+                match serde_json::from_str::<ChatChunk>(&event.data) {
+                    Ok(chunk) => chunk.choices.into_iter().next()?.delta.content.map(Ok),
+                    Err(err) => Some(Err(anyhow!("invalid chat completion chunk `{}`: {}", event.data, err))),
+                }
+            });
+
+        Ok(deltas.boxed())
+    }
+
+    async fn chat(&self, messages: &[ChatMessage]) -> anyhow::Result<String> {
+        let message = self.complete(&self.request(messages, false)).await?;
+        Ok(message.content.unwrap_or_default())
+    }
+
+    /// Call a tool with `tool_choice: required`, the functions are in the OpenAI format.
+    async fn call_function(&self, messages: &[ChatMessage], functions: &Value) -> anyhow::Result<FunctionCall> {
+        let request = ChatRequest {
+            tools: Some(functions),
+            tool_choice: Some("required"),
+            ..self.request(messages, false)
+        };
+        let message = self.complete(&request).await?;
+        message.tool_calls.into_iter()
+            .next()
+            .map(|call| call.function)
+            .ok_or(anyhow!("the model called no tool: {}", message.content.unwrap_or_default()))
+    }
+}
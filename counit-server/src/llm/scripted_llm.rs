@@ -0,0 +1,55 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use futures::stream::{self, BoxStream};
+use futures::StreamExt;
+use serde_json::Value;
+
+use crate::llm::{ChatMessage, FunctionCall, LlmClient};
+
+/// A model which replies with the given completions in order, and keeps the conversations it
+/// was sent, so that the agent and the prompts can be tested without a model.
+pub struct ScriptedLlm {
+    replies: Mutex<VecDeque<String>>,
+    requests: Mutex<Vec<Vec<ChatMessage>>>,
+}
+
+impl ScriptedLlm {
+    pub fn new(replies: Vec<&str>) -> Self {
+        ScriptedLlm {
+            replies: Mutex::new(replies.into_iter().map(|it| it.to_string()).collect()),
+            requests: Mutex::new(vec![]),
+        }
+    }
+
+    fn next_reply(&self, messages: &[ChatMessage]) -> Result<String> {
+        self.requests.lock().unwrap().push(messages.to_vec());
+        self.replies.lock().unwrap()
+            .pop_front()
+            .ok_or(anyhow!("the script has no reply left"))
+    }
+
+    /// The conversations sent to the model, in order.
+    pub fn requests(&self) -> Vec<Vec<ChatMessage>> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for ScriptedLlm {
+    /// Stream the next reply word by word, like a model would.
+    async fn chat_stream(&self, messages: &[ChatMessage]) -> Result<BoxStream<'static, Result<String>>> {
+        let reply = self.next_reply(messages)?;
+
+        let deltas: Vec<Result<String>> = reply.split_inclusive(' ')
+            .map(|it| Ok(it.to_string()))
+            .collect();
+        Ok(stream::iter(deltas).boxed())
+    }
+
+    /// The next reply is the JSON of the call, like `{"name": "code", "arguments": {"query": "payment"}}`.
+    async fn call_function(&self, messages: &[ChatMessage], _functions: &Value) -> Result<FunctionCall> {
+        Ok(serde_json::from_str(&self.next_reply(messages)?)?)
+    }
+}
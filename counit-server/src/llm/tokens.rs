@@ -0,0 +1,115 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use tracing::{info, warn};
+
+/// Counts the tokens of the prompts, to fit them in the context window of the model.
+pub enum TokenCounter {
+    /// A `tokenizer.json` of the tokenizers crate
+    Tokenizer(Arc<tokenizers::Tokenizer>),
+    /// About 4 characters per token, and a token per CJK character
+    Estimate,
+}
+
+impl TokenCounter {
+    /// The tokenizer of the file, or of the embedding model if no file is given, or an estimate
+    /// if the tokenizer can't be loaded.
+    pub fn load(tokenizer_file: Option<&Path>, model_dir: &Path) -> Self {
+        let path = tokenizer_file.map(|it| it.to_path_buf())
+            .unwrap_or_else(|| model_dir.join("tokenizer.json"));
+        if !path.exists() {
+            warn!(?path, "no tokenizer for the prompts, estimating their tokens");
+            return TokenCounter::Estimate;
+        }
+
+        let tokenizer = tokenizers::Tokenizer::from_file(&path)
+            .and_then(|mut tokenizer| {
+                // the tokenizers of embedding models truncate their input
+                tokenizer.with_truncation(None)?;
+                Ok(tokenizer)
+            });
+        match tokenizer {
+            Ok(tokenizer) => {
+                info!(?path, "prompt tokens counted with tokenizer");
+                TokenCounter::Tokenizer(Arc::new(tokenizer))
+            }
+            Err(err) => {
+                warn!(?path, %err, "failed to load the tokenizer of the prompts, estimating their tokens");
+                TokenCounter::Estimate
+            }
+        }
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            TokenCounter::Tokenizer(tokenizer) => match tokenizer.encode(text, false) {
+                Ok(encoding) => encoding.len(),
+                Err(_) => estimate(text),
+            },
+            TokenCounter::Estimate => estimate(text),
+        }
+    }
+
+    /// The beginning of the text which fits in `max_tokens`.
+    pub fn truncate<'a>(&self, text: &'a str, max_tokens: usize) -> &'a str {
+        if self.count(text) <= max_tokens {
+            return text;
+        }
+
+        let end = match self {
+            TokenCounter::Tokenizer(tokenizer) => tokenizer.encode(text, false).ok()
+                .and_then(|encoding| {
+                    let offsets = encoding.get_offsets();
+                    if max_tokens == 0 { Some(0) } else { offsets.get(max_tokens - 1).map(|(_, end)| *end) }
+                }),
+            TokenCounter::Estimate => None,
+        };
+        let end = end.filter(|it| text.is_char_boundary(*it))
+            .unwrap_or_else(|| estimated_end(text, max_tokens));
+
+        &text[..end]
+    }
+}
+
+fn is_cjk(char: char) -> bool {
+    matches!(char as u32, 0x3000..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF)
+}
+
+fn estimate(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0usize, 0usize), |(cjk, other), char| {
+        if is_cjk(char) { (cjk + 1, other) } else { (cjk, other + 1) }
+    });
+    cjk + other.div_ceil(4)
+}
+
+/// The end of the text with about `max_tokens` tokens, by the estimate.
+fn estimated_end(text: &str, max_tokens: usize) -> usize {
+    let mut budget = max_tokens * 4;
+    for (index, char) in text.char_indices() {
+        let cost = if is_cjk(char) { 4 } else { 1 };
+        if cost > budget {
+            return index;
+        }
+        budget -= cost;
+    }
+
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::llm::tokens::TokenCounter;
+
+    #[test]
+    fn should_estimate_and_truncate_tokens() {
+        let counter = TokenCounter::Estimate;
+        assert_eq!(counter.count("fn main() {}"), 3);
+        assert_eq!(counter.count("统一收单"), 4);
+        assert_eq!(counter.count(""), 0);
+
+        assert_eq!(counter.truncate("fn main() {}", 2), "fn main(");
+        assert_eq!(counter.truncate("统一收单交易", 2), "统一");
+        assert_eq!(counter.truncate("fn", 2), "fn");
+        assert_eq!(counter.truncate("fn", 0), "");
+    }
+}
@@ -1,14 +1,14 @@
 use std::net::SocketAddr;
 
-use axum::{Extension, Router, routing::get};
+use axum::{Extension, Json, Router, routing::get};
 use axum::extract::DefaultBodyLimit;
 use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::cors::CorsLayer;
 use tracing::info;
 
 use crate::application::Application;
-use crate::configuration::Configuration;
-use crate::server::{agent_api, archguard_api, semantic_api, domain_api};
+use crate::configuration::{CONFIG_FILE, Configuration};
+use crate::server::{agent_api, analyser_api, archguard_api, semantic_api, domain_api, graph_api, path_api, example_api, mcp_api, model_api, openai_api};
 
 pub mod server;
 pub mod model;
@@ -18,42 +18,67 @@ pub mod configuration;
 pub mod agent;
 pub mod dsl;
 pub mod domain;
+pub mod graph;
+pub mod llm;
+pub mod mcp;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt::init();
+    // with `--mcp-stdio`, stdout carries the MCP messages instead of serving the api
+    let mcp_stdio = std::env::args().any(|it| it == "--mcp-stdio");
+    if mcp_stdio {
+        tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
 
     // load configuration from public/config.json if exists
-    let config_file = std::path::Path::new("public/config.json");
+    let config_file = std::path::Path::new(CONFIG_FILE);
 
     let mut config: Configuration;
-    if config_file.exists() {
-        config = serde_json::from_str(&std::fs::read_to_string(config_file)?)?;
+    let loaded_file = if config_file.exists() {
+        config = Configuration::from_file(config_file)?;
         info!("Configuration loaded from public/config.json");
+        Some(config_file.to_path_buf())
     } else {
         // for development only
-        config = Configuration::default()
-    }
+        config = Configuration::default();
+        None
+    };
 
     let bind = SocketAddr::new(config.host.parse()?, config.port);
-    let app = Application::initialize(config).await?;
+    let app = Application::initialize(config, loaded_file).await?;
+
+    if mcp_stdio {
+        return mcp::stdio::serve(app).await;
+    }
 
     let mut api = Router::new().with_state(app.clone())
         .route("/", get(root))
         // core api for query
         .route("/query", get(semantic_api::query))
         .route("/text-embedding", get(semantic_api::embedding))
+        .route("/paths", get(path_api::paths))
 
         // the agent api
         .nest("/agent", agent_api::router())
+        .nest("/explain/examples", example_api::router())
 
         .nest("/domain", domain_api::router())
+        .nest("/model", model_api::router())
+
+        .nest("/graph", graph_api::router())
+
+        // the Model Context Protocol server for the IDE assistants
+        .nest("/mcp", mcp_api::router())
 
         //align to archguard api
         .nest("/scanner", archguard_api::router())
+        .nest("/analyser", analyser_api::router())
         ;
 
     api = api.route("/health", get(health));
+    api = api.route("/ready", get(ready));
 
     let api = api
         .layer(Extension(app.clone()))
@@ -62,7 +87,12 @@ async fn main() -> anyhow::Result<()> {
         .layer(CatchPanicLayer::new());
 
     let mut router = Router::new()
-        .nest("/api", api);
+        .nest("/api", api)
+        // OpenAI-compatible chat completions, for the existing chat clients
+        .nest("/v1", openai_api::router()
+            .layer(Extension(app.clone()))
+            .layer(CorsLayer::permissive())
+            .layer(CatchPanicLayer::new()));
 
     info!(%bind, "starting webserver");
 
@@ -82,6 +112,26 @@ async fn health(Extension(app): Extension<Application>) -> String {
     return serde_json::to_string::<Configuration>(&*app.config.clone()).unwrap();
 }
 
+/// Whether the server is ready, `degraded` when records of the glossary were skipped or its files
+/// can't be loaded.
+async fn ready(Extension(app): Extension<Application>) -> Json<serde_json::Value> {
+    let report = app.transpiler.report();
+    let status = if report.is_degraded() { "degraded" } else { "ready" };
+
+    Json(serde_json::json!({
+        "status": status,
+        "glossary": {
+            "records": report.records,
+            "files": report.files.len(),
+            "diagnostics": report.diagnostic_count(),
+            "skipped_records": report.skipped_records(),
+            "failed_files": report.failed_files(),
+        },
+        "semantic": app.semantic.is_some(),
+        "llm": app.model.current().client.is_some(),
+    }))
+}
+
 async fn root() -> &'static str {
     "Hello, World!"
 }
@@ -0,0 +1,247 @@
+//! A Model Context Protocol server, so that the assistants of the IDEs can search the code, the
+//! glossary and the service graph of CoUnit. [McpServer] handles the JSON-RPC messages, which
+//! are carried by [stdio] or by the SSE routes of [crate::server::mcp_api].
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, warn};
+
+use crate::application::Application;
+use crate::mcp::protocol::{
+    CallToolResult, INVALID_REQUEST, JsonRpcError, JsonRpcRequest, JsonRpcResponse, METHOD_NOT_FOUND,
+    PARSE_ERROR, Resource, RESOURCE_NOT_FOUND, ResourceContent, TextContent,
+};
+
+pub mod protocol;
+pub mod stdio;
+pub mod tools;
+
+/// The version of the protocol with the SSE transport.
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+const OPENAPI_SCHEME: &str = "openapi://";
+
+#[derive(Clone)]
+pub struct McpServer {
+    app: Application,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallToolParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadResourceParams {
+    uri: String,
+}
+
+impl McpServer {
+    pub fn new(app: Application) -> Self {
+        McpServer { app }
+    }
+
+    /// Handle a message of the client, returns the response to send back if it is a request.
+    pub async fn handle_message(&self, message: &str) -> Option<JsonRpcResponse> {
+        let value: Value = match serde_json::from_str(message) {
+            Ok(value) => value,
+            Err(err) => return Some(JsonRpcResponse::new(Value::Null, Err(JsonRpcError::new(PARSE_ERROR, err.to_string())))),
+        };
+
+        // a response of the client, we don't send requests so there is nothing to do with it
+        if value.get("method").is_none() && (value.get("result").is_some() || value.get("error").is_some()) {
+            return None;
+        }
+
+        match serde_json::from_value::<JsonRpcRequest>(value) {
+            Ok(request) => self.handle(request).await,
+            Err(err) => Some(JsonRpcResponse::new(Value::Null, Err(JsonRpcError::new(INVALID_REQUEST, err.to_string())))),
+        }
+    }
+
+    pub async fn handle(&self, request: JsonRpcRequest) -> Option<JsonRpcResponse> {
+        let Some(id) = request.id else {
+            debug!(method = request.method, "mcp notification");
+            return None;
+        };
+
+        let params = request.params.unwrap_or(Value::Null);
+        let result = match request.method.as_str() {
+            "initialize" => Ok(self.initialize()),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools::definitions() })),
+            "tools/call" => self.call_tool(params).await,
+            "resources/list" => Ok(json!({ "resources": self.list_resources() })),
+            "resources/read" => self.read_resource(params),
+            method => Err(JsonRpcError::new(METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        };
+
+        Some(JsonRpcResponse::new(id, result))
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {}, "resources": {} },
+            "serverInfo": { "name": "counit", "version": env!("CARGO_PKG_VERSION") },
+        })
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let params: CallToolParams = serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        if !tools::definitions().iter().any(|it| it.name == params.name) {
+            return Err(JsonRpcError::invalid_params(format!("unknown tool {}", params.name)));
+        }
+
+        let result = match tools::call(&self.app, &params.name, params.arguments).await {
+            Ok(text) => CallToolResult { content: vec![TextContent::new(text)], is_error: false },
+            Err(err) => {
+                warn!(tool = params.name, %err, "mcp tool failed");
+                CallToolResult { content: vec![TextContent::new(err.to_string())], is_error: true }
+            }
+        };
+
+        Ok(json!(result))
+    }
+
+    /// The OpenAPI documents of the analysed repos, as `openapi://<system>/<repo>`.
+    fn list_resources(&self) -> Vec<Resource> {
+        self.app.openapi.keys().into_iter()
+            .filter_map(|(system_id, repo_id)| {
+                let document = self.app.openapi.get(&system_id, &repo_id)?;
+                Some(Resource {
+                    uri: format!("{}{}/{}", OPENAPI_SCHEME, system_id, repo_id),
+                    name: format!("{} ({}/{})", document.info.title, system_id, repo_id),
+                    description: Some(format!("{} operations of the OpenAPI document", document.display_texts().len())),
+                    mime_type: "application/json",
+                })
+            })
+            .collect()
+    }
+
+    fn read_resource(&self, params: Value) -> Result<Value, JsonRpcError> {
+        let params: ReadResourceParams = serde_json::from_value(params).map_err(JsonRpcError::invalid_params)?;
+        let document = params.uri.strip_prefix(OPENAPI_SCHEME)
+            .and_then(|it| it.split_once('/'))
+            .and_then(|(system_id, repo_id)| self.app.openapi.get(system_id, repo_id));
+        let Some(document) = document else {
+            return Err(JsonRpcError::new(RESOURCE_NOT_FOUND, format!("resource {} not found", params.uri)));
+        };
+
+        let text = serde_json::to_string_pretty(&document)
+            .map_err(|err| JsonRpcError::new(protocol::INTERNAL_ERROR, err.to_string()))?;
+        let content = ResourceContent { uri: params.uri, mime_type: "application/json", text };
+        Ok(json!({ "contents": [content] }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+
+    use crate::application::Application;
+    use crate::configuration::Configuration;
+    use crate::domain::domain_transpiler::DomainTranspiler;
+    use crate::llm::model::Model;
+    use crate::mcp::McpServer;
+    use crate::mcp::protocol::{METHOD_NOT_FOUND, PARSE_ERROR, RESOURCE_NOT_FOUND};
+    use crate::model::openapi_document::{Components, Info, OpenApiDocument};
+
+    fn new_server() -> McpServer {
+        let domain_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
+            .join("_fixtures")
+            .join("domain");
+        let config = Configuration::default();
+        let model = Model::new(&config, None);
+        let app = Application {
+            transpiler: Arc::new(DomainTranspiler::new(domain_dir)),
+            ..Application::for_tests(config, model)
+        };
+
+        app.paths.add("payment", ["src/main/java/com/pay/PaymentController.java", "README.md"]);
+        app.openapi.save("finance", "payment", OpenApiDocument {
+            openapi: "3.0.1".to_string(),
+            info: Info { title: "payment".to_string(), version: "1.0.0".to_string() },
+            paths: BTreeMap::new(),
+            components: Components::default(),
+        });
+        McpServer::new(app)
+    }
+
+    async fn request(server: &McpServer, method: &str, params: Value) -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }).to_string();
+        let response = server.handle_message(&message).await.unwrap();
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_initialize_and_list_tools() {
+        let server = new_server();
+
+        let response = request(&server, "initialize", json!({ "protocolVersion": "2024-11-05" })).await;
+        assert_eq!(response["result"]["serverInfo"]["name"], "counit");
+        assert!(server.handle_message(r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#).await.is_none());
+
+        let response = request(&server, "tools/list", Value::Null).await;
+        let names: Vec<&str> = response["result"]["tools"].as_array().unwrap().iter()
+            .map(|it| it["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["semantic_query", "path_search", "glossary_lookup", "service_graph"]);
+
+        let response = request(&server, "unknown/method", Value::Null).await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let response = server.handle_message("{").await.unwrap();
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn should_call_tools() {
+        let server = new_server();
+
+        let response = request(&server, "tools/call", json!({
+            "name": "path_search",
+            "arguments": { "query": "payment controller" }
+        })).await;
+        assert_eq!(response["result"]["isError"], false);
+        assert!(response["result"]["content"][0]["text"].as_str().unwrap().contains("PaymentController.java"));
+
+        let response = request(&server, "tools/call", json!({
+            "name": "glossary_lookup",
+            "arguments": { "text": "本币" }
+        })).await;
+        let text: Value = serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text["transpiled"], "本币(Domestic Currency)");
+        assert_eq!(text["terms"][0]["end"], 6);
+        assert_eq!(text["records"][0]["abbreviation"], "DCY");
+
+        // semantic search is disabled, the model is told so
+        let response = request(&server, "tools/call", json!({
+            "name": "semantic_query",
+            "arguments": { "query": "create a payment" }
+        })).await;
+        assert_eq!(response["result"]["isError"], true);
+
+        let response = request(&server, "tools/call", json!({ "name": "unknown", "arguments": {} })).await;
+        assert!(response["error"].is_object());
+    }
+
+    #[tokio::test]
+    async fn should_read_openapi_resources() {
+        let server = new_server();
+
+        let response = request(&server, "resources/list", Value::Null).await;
+        assert_eq!(response["result"]["resources"][0]["uri"], "openapi://finance/payment");
+
+        let response = request(&server, "resources/read", json!({ "uri": "openapi://finance/payment" })).await;
+        let text = response["result"]["contents"][0]["text"].as_str().unwrap();
+        assert!(text.contains("3.0.1"));
+
+        let response = request(&server, "resources/read", json!({ "uri": "openapi://finance/unknown" })).await;
+        assert_eq!(response["error"]["code"], RESOURCE_NOT_FOUND);
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Result;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::info;
+
+use crate::application::Application;
+use crate::mcp::McpServer;
+
+/// Serve MCP over stdin and stdout, one JSON-RPC message per line, until stdin is closed.
+/// Nothing else may be written to stdout, the logs go to stderr.
+pub async fn serve(app: Application) -> Result<()> {
+    let server = McpServer::new(app);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    info!("serving MCP over stdio");
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = server.handle_message(&line).await {
+            let mut message = serde_json::to_string(&response)?;
+            message.push('\n');
+            stdout.write_all(message.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    Ok(())
+}
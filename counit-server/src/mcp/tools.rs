@@ -0,0 +1,192 @@
+use std::borrow::Cow;
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::application::Application;
+use crate::graph::call_graph::Graph;
+use crate::graph::graph_render::DiagramFormat;
+use crate::repository::literal::Literal;
+use crate::repository::payload::PayloadType;
+use crate::repository::semantic_query::SemanticQuery;
+use crate::mcp::protocol::ToolDefinition;
+
+pub const SEMANTIC_QUERY: &str = "semantic_query";
+pub const PATH_SEARCH: &str = "path_search";
+pub const GLOSSARY_LOOKUP: &str = "glossary_lookup";
+pub const SERVICE_GRAPH: &str = "service_graph";
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+pub fn definitions() -> Vec<ToolDefinition> {
+    let string_array = |description: &str| json!({ "type": "array", "items": { "type": "string" }, "description": description });
+
+    vec![
+        ToolDefinition {
+            name: SEMANTIC_QUERY,
+            description: "Search the indexed code, docs and APIs of the organization by meaning.",
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "what to search, in natural language or code terms" },
+                    "type": {
+                        "type": "string",
+                        "enum": ["Code", "Comment", "Doc", "HttpApi", "OpenApi", "DatabaseMap"],
+                        "description": "the kind of snippets, Code by default"
+                    },
+                    "repos": string_array("only the snippets of these repos"),
+                    "paths": string_array("only the snippets of these paths"),
+                    "langs": string_array("only the snippets of these languages"),
+                    "limit": { "type": "integer", "description": "max snippets, 10 by default" }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: PATH_SEARCH,
+            description: "Find the files of the indexed repos whose path matches every term of the query.",
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "terms of the path, like `payment controller`" },
+                    "repos": string_array("only the paths of these repos"),
+                    "limit": { "type": "integer", "description": "max paths, 10 by default" }
+                },
+                "required": ["query"]
+            }),
+        },
+        ToolDefinition {
+            name: GLOSSARY_LOOKUP,
+            description: "Look up the domain terms of a text in the glossary, with their English names and abbreviations.",
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string", "description": "a question or a text with domain terms" }
+                },
+                "required": ["text"]
+            }),
+        },
+        ToolDefinition {
+            name: SERVICE_GRAPH,
+            description: "Draw the calls between the services, or the tables used by the code, as reported by ArchGuard.",
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "kind": { "type": "string", "enum": ["services", "datamap"], "description": "services by default" },
+                    "system": { "type": "string", "description": "the ArchGuard system id, all systems when empty" },
+                    "repo": { "type": "string", "description": "start from the nodes of this repo" },
+                    "depth": { "type": "integer", "description": "max hops from the nodes of `repo`" },
+                    "format": { "type": "string", "enum": ["mermaid", "plantuml", "dot"], "description": "mermaid by default" }
+                }
+            }),
+        },
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+struct SemanticQueryArgs {
+    query: String,
+    #[serde(default)]
+    r#type: PayloadType,
+    #[serde(default)]
+    repos: Vec<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    langs: Vec<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathSearchArgs {
+    query: String,
+    #[serde(default)]
+    repos: Vec<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlossaryLookupArgs {
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum GraphKind {
+    #[default]
+    Services,
+    Datamap,
+}
+
+#[derive(Debug, Deserialize)]
+struct ServiceGraphArgs {
+    #[serde(default)]
+    kind: GraphKind,
+    system: Option<String>,
+    repo: Option<String>,
+    depth: Option<usize>,
+    #[serde(default)]
+    format: DiagramFormat,
+}
+
+/// Call the tool, the output is the text given to the model.
+pub async fn call(app: &Application, name: &str, arguments: Value) -> Result<String> {
+    match name {
+        SEMANTIC_QUERY => semantic_query(app, parse(arguments)?).await,
+        PATH_SEARCH => path_search(app, parse(arguments)?),
+        GLOSSARY_LOOKUP => glossary_lookup(app, parse(arguments)?),
+        SERVICE_GRAPH => service_graph(app, parse(arguments)?),
+        _ => bail!("unknown tool {}", name),
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|err| anyhow!("invalid arguments: {}", err))
+}
+
+fn limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+}
+
+async fn semantic_query(app: &Application, args: SemanticQueryArgs) -> Result<String> {
+    let Some(semantic) = app.semantic.as_ref() else {
+        bail!("semantic search is disabled because `qdrant_url` is not provided");
+    };
+
+    let literals = |values: Vec<String>| values.into_iter().map(|it| Literal::Plain(Cow::Owned(it))).collect();
+    let query = SemanticQuery {
+        repos: literals(args.repos),
+        paths: literals(args.paths),
+        langs: args.langs.into_iter().map(Cow::Owned).collect(),
+        ..SemanticQuery::from_str(args.query, args.r#type)
+    };
+
+    let results = semantic.search(&query, limit(args.limit) as u64, 0, 0.0, false).await?;
+    Ok(serde_json::to_string_pretty(&results)?)
+}
+
+fn path_search(app: &Application, args: PathSearchArgs) -> Result<String> {
+    let matches = app.paths.search(&args.query, &args.repos, limit(args.limit));
+    Ok(serde_json::to_string_pretty(&matches)?)
+}
+
+fn glossary_lookup(app: &Application, args: GlossaryLookupArgs) -> Result<String> {
+    let transpiled = app.transpiler.annotate(&args.text);
+    let output = json!({
+        "transpiled": transpiled.text,
+        "terms": transpiled.terms,
+        "records": app.transpiler.lookup(&args.text),
+    });
+    Ok(serde_json::to_string_pretty(&output)?)
+}
+
+fn service_graph(app: &Application, args: ServiceGraphArgs) -> Result<String> {
+    let graph = match args.kind {
+        GraphKind::Services => Graph::from_services(&app.graph.services(args.system.as_deref())),
+        GraphKind::Datamap => Graph::from_datamaps(&app.graph.datamaps(args.system.as_deref())),
+    };
+
+    Ok(graph.neighbourhood(args.repo.as_deref(), args.depth).render(args.format))
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
-use crate::model::{CodeDataStruct, CodeFunction, ContainerDemand, ContainerService, ContainerSupply};
-use crate::model::analyser::ApiAnalyser;
+use crate::model::{CodeAnnotation, CodeCall, CodeDataStruct, CodeFunction, ContainerDemand, ContainerService, ContainerSupply};
+use crate::model::analyser::{annotation_value, ApiAnalyser, http_method_by_prefix, join_url};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct JavaApiAnalyser {
@@ -11,9 +11,10 @@ pub struct JavaApiAnalyser {
 impl ApiAnalyser for JavaApiAnalyser {
     fn analysis_by_node(&mut self, node: &CodeDataStruct, workspace: String) {
         let route_annotation = node.filter_annotations(vec!["RestController", "Controller", "RequestMapping"]);
+        let feign_annotation = node.filter_annotations(vec!["FeignClient"]);
 
-        // 1. create resources
-        if !route_annotation.is_empty() {
+        // 1. create resources, a Feign client declares the resources of another service
+        if !route_annotation.is_empty() && feign_annotation.is_empty() {
             let mut base_url = String::new();
             let mapping_annotation = node.filter_annotations(vec!["RequestMapping"]);
             if !mapping_annotation.is_empty() && !mapping_annotation[0].key_values.is_empty() {
@@ -33,6 +34,19 @@ impl ApiAnalyser for JavaApiAnalyser {
                 let _ = self.create_demand(func.clone(), node.clone());
             }
         }
+
+        // 3. Spring Cloud OpenFeign
+        if let Some(feign) = feign_annotation.first() {
+            self.create_feign_demand(feign, node);
+        }
+
+        // 4. Spring WebFlux WebClient
+        let use_web_client = node.imports.iter().any(|import| import.source.ends_with(".WebClient"));
+        if use_web_client {
+            for func in &node.functions {
+                self.create_web_client_demand(func, node);
+            }
+        }
     }
 
     fn to_container_services(&self) -> Vec<ContainerService> {
@@ -64,23 +78,7 @@ impl JavaApiAnalyser {
             }
 
             if node_name == "RestTemplate" && node_name != "<init>" {
-                let mut method = String::new();
-                let lowercase = function_name.to_lowercase();
-                match &*lowercase {
-                    x if x.starts_with("get") => {
-                        method = "Get".to_string();
-                    }
-                    x if x.starts_with("post") => {
-                        method = "Post".to_string();
-                    }
-                    x if x.starts_with("delete") => {
-                        method = "Delete".to_string();
-                    }
-                    x if x.starts_with("put") => {
-                        method = "Put".to_string();
-                    }
-                    _ => {}
-                }
+                let method = http_method_by_prefix(&function_name).unwrap_or_default();
 
                 let mut url = String::new();
                 if !call.parameters.is_empty() && !call.parameters[0].type_value.is_empty() {
@@ -103,75 +101,116 @@ impl JavaApiAnalyser {
         Ok(())
     }
 
-    fn create_resource(&mut self, func: CodeFunction, base_url: &str, node: CodeDataStruct) {
-        let mut http_method = String::new();
-        let mut route = base_url.to_string();
-        for annotation in &func.annotations {
-            let mut is_http_annotation = true;
-            match &annotation.name[..] {
-                "GetMapping" => http_method = "Get".to_string(),
-                "PostMapping" => http_method = "Post".to_string(),
-                "DeleteMapping" => http_method = "Delete".to_string(),
-                "PutMapping" => http_method = "Put".to_string(),
-                "PatchMapping" => http_method = "Patch".to_string(),
-                _ => is_http_annotation = false,
-            }
+    /// `@FeignClient(name = "payment", url = "${payment.url}", path = "/api")` on an interface, every
+    /// mapping method of the interface is a call to the remote service.
+    fn create_feign_demand(&mut self, feign: &CodeAnnotation, node: &CodeDataStruct) {
+        let attribute = |keys: &[&str]| -> Option<String> {
+            feign.key_values.iter()
+                .find(|kv| keys.contains(&kv.key.as_str()))
+                .map(|kv| kv.value.trim_matches('"').to_string())
+        };
 
-            let has_sub_url_mapping = !annotation.key_values.is_empty();
-            if is_http_annotation && !http_method.is_empty() && has_sub_url_mapping {
-                let sub_url = annotation.key_values[0].value.clone();
-                let pure_url = sub_url.trim_matches('"').to_string();
+        let base = attribute(&["url"])
+            .or_else(|| annotation_value(feign, &["name", "value"]))
+            .unwrap_or_default();
+        let path = attribute(&["path"]).unwrap_or_default();
 
-                if !base_url.is_empty() {
-                    route = format!("{}{}", base_url, pure_url);
-                } else {
-                    route = pure_url;
-                }
+        for func in &node.functions {
+            if let Some((method, sub_url)) = func.annotations.iter().find_map(spring_mapping) {
+                self.demands.push(ContainerDemand {
+                    source_caller: format!("{}.{}", node.node_name, func.name),
+                    call_routes: vec![],
+                    base: base.clone(),
+                    target_url: join_url(&path, &sub_url),
+                    target_http_method: method,
+                    call_data: "".to_string(),
+                });
             }
+        }
+    }
 
-            // todo: split by class
-            // case 2
-            if annotation.name == "RequestMapping" {
-                let opt_url = annotation.key_values.iter().find(|kv| kv.key == "value");
-                let opt_method = annotation.key_values.iter().find(|kv| kv.key == "method");
-                if let (Some(url), Some(method)) = (opt_url, opt_method) {
-                    match method.value.as_str() {
-                        "RequestMethod.GET" | "GET" => http_method = "Get".to_string(),
-                        "RequestMethod.POST" | "POST" => http_method = "Post".to_string(),
-                        "RequestMethod.DELETE" | "DELETE" => http_method = "Delete".to_string(),
-                        "RequestMethod.PUT" | "PUT" => http_method = "Put".to_string(),
-                        "RequestMethod.PATCH" | "PATCH" => http_method = "Patch".to_string(),
-                        _ => {}
-                    }
+    /// WebClient calls are fluent chains, like `webClient.get().uri("/api/{id}", id).retrieve()`,
+    /// which Chapi reports as consecutive calls: the verb call is followed by the `uri` call.
+    fn create_web_client_demand(&mut self, func: &CodeFunction, node: &CodeDataStruct) {
+        let web_client_fields: Vec<&str> = node.fields.iter()
+            .filter(|field| field.type_type.as_deref().is_some_and(|it| it.ends_with("WebClient")))
+            .filter_map(|field| field.type_key.as_deref())
+            .collect();
+        let is_web_client = |call: &CodeCall| {
+            call.node_name.ends_with("WebClient")
+                || web_client_fields.contains(&call.node_name.as_str())
+                || web_client_fields.contains(&call.origin_node_name.as_str())
+        };
+
+        let mut base = String::new();
+        let mut method: Option<String> = None;
+        for call in &func.function_calls {
+            let first_param = call.parameters.first()
+                .map(|param| param.type_value.trim_matches('"').to_string())
+                .unwrap_or_default();
 
-                    let pure_url = url.value.trim_matches('"').to_string();
-                    if !base_url.is_empty() {
-                        route = format!("{}{}", base_url, pure_url);
-                    } else {
-                        route = pure_url;
+            match call.function_name.as_str() {
+                "create" | "baseUrl" if is_web_client(call) || call.node_name.ends_with("Builder") => {
+                    base = first_param;
+                }
+                "get" | "post" | "put" | "delete" | "patch" if is_web_client(call) => {
+                    method = http_method_by_prefix(&call.function_name);
+                }
+                "method" if is_web_client(call) => {
+                    method = http_method_by_prefix(first_param.trim_start_matches("HttpMethod."));
+                }
+                "uri" => {
+                    if let Some(method) = method.take() {
+                        self.demands.push(ContainerDemand {
+                            source_caller: format!("{}.{}", node.node_name, func.name),
+                            call_routes: vec![],
+                            base: base.clone(),
+                            target_url: first_param,
+                            target_http_method: method,
+                            call_data: "".to_string(),
+                        });
                     }
                 }
+                _ => {}
             }
         }
+    }
 
-        if !http_method.is_empty() {
-            if !route.starts_with('/') {
-                route = format!("/{}", route);
-            }
-
-            route = route.replace("//", "/");
-
+    fn create_resource(&mut self, func: CodeFunction, base_url: &str, node: CodeDataStruct) {
+        if let Some((http_method, sub_url)) = func.annotations.iter().find_map(spring_mapping) {
             self.resources.push(ContainerSupply {
-                source_url: route.clone(),
-                source_http_method: http_method.clone(),
+                source_url: join_url(base_url, &sub_url),
+                source_http_method: http_method,
                 package_name: node.package.clone(),
                 class_name: node.node_name.clone(),
                 method_name: func.name.clone(),
+                produces: vec![],
+                consumes: vec![],
             });
         }
     }
 }
 
+/// The http method and url of a Spring mapping annotation, for example: `@GetMapping("/{id}")` or
+/// `@RequestMapping(value = "/{id}", method = RequestMethod.GET)`.
+pub(crate) fn spring_mapping(annotation: &CodeAnnotation) -> Option<(String, String)> {
+    let http_method = match &annotation.name[..] {
+        "GetMapping" => "Get".to_string(),
+        "PostMapping" => "Post".to_string(),
+        "DeleteMapping" => "Delete".to_string(),
+        "PutMapping" => "Put".to_string(),
+        "PatchMapping" => "Patch".to_string(),
+        "RequestMapping" => {
+            let method = annotation.key_values.iter().find(|kv| kv.key == "method")?;
+            http_method_by_prefix(method.value.trim_start_matches("RequestMethod."))?
+        }
+        _ => return None,
+    };
+
+    let url = annotation_value(annotation, &["value", "path"]).unwrap_or_default();
+    Some((http_method, url))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::model::analyser::api_analyser::JavaApiAnalyser;
@@ -263,4 +302,102 @@ mod tests {
         assert_eq!(supply.class_name, "HelloController");
         assert_eq!(supply.method_name, "index");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_create_feign_demands() {
+        let demo_code = r#"[
+  {
+    "NodeName": "PaymentClient",
+    "Package": "com.example.order",
+    "Annotations": [
+      {
+        "Name": "FeignClient",
+        "KeyValues": [
+          { "Key": "name", "Value": "\"payment\"" },
+          { "Key": "path", "Value": "\"/api/payments\"" }
+        ]
+      }
+    ],
+    "Functions": [
+      {
+        "Name": "pay",
+        "Annotations": [
+          { "Name": "PostMapping", "KeyValues": [{ "Key": "\"/{orderId}\"", "Value": "\"/{orderId}\"" }] }
+        ]
+      },
+      {
+        "Name": "refund",
+        "Annotations": [
+          {
+            "Name": "RequestMapping",
+            "KeyValues": [
+              { "Key": "value", "Value": "\"/{orderId}/refund\"" },
+              { "Key": "method", "Value": "RequestMethod.PUT" }
+            ]
+          }
+        ]
+      }
+    ]
+  }
+]"#;
+
+        let mut analyser = JavaApiAnalyser::new();
+        let codes: Vec<CodeDataStruct> = serde_json::from_str(demo_code).unwrap();
+        analyser.analysis_by_node(&codes[0], "".to_string());
+        let service = &analyser.to_container_services()[0];
+
+        assert!(service.resources.is_empty());
+        assert_eq!(service.demands.len(), 2);
+        assert_eq!(service.demands[0].base, "payment");
+        assert_eq!(service.demands[0].source_caller, "PaymentClient.pay");
+        assert_eq!(service.demands[0].target_url, "/api/payments/{orderId}");
+        assert_eq!(service.demands[0].target_http_method, "Post");
+        assert_eq!(service.demands[1].target_url, "/api/payments/{orderId}/refund");
+        assert_eq!(service.demands[1].target_http_method, "Put");
+    }
+
+    #[test]
+    fn should_create_web_client_demands() {
+        let demo_code = r#"[
+  {
+    "NodeName": "UserGateway",
+    "Package": "com.example.order",
+    "Fields": [
+      { "TypeType": "WebClient", "TypeKey": "webClient" }
+    ],
+    "Imports": [
+      { "Source": "org.springframework.web.reactive.function.client.WebClient" }
+    ],
+    "Functions": [
+      {
+        "Name": "findUser",
+        "FunctionCalls": [
+          { "NodeName": "webClient", "FunctionName": "get" },
+          { "NodeName": "RequestHeadersUriSpec", "FunctionName": "uri", "Parameters": [{ "TypeValue": "\"/api/users/{id}\"", "TypeType": "" }, { "TypeValue": "id", "TypeType": "" }] },
+          { "NodeName": "RequestHeadersSpec", "FunctionName": "retrieve" }
+        ]
+      },
+      {
+        "Name": "createUser",
+        "FunctionCalls": [
+          { "NodeName": "WebClient", "FunctionName": "method", "Parameters": [{ "TypeValue": "HttpMethod.POST", "TypeType": "" }] },
+          { "NodeName": "RequestBodyUriSpec", "FunctionName": "uri", "Parameters": [{ "TypeValue": "\"/api/users\"", "TypeType": "" }] }
+        ]
+      }
+    ]
+  }
+]"#;
+
+        let mut analyser = JavaApiAnalyser::new();
+        let codes: Vec<CodeDataStruct> = serde_json::from_str(demo_code).unwrap();
+        analyser.analysis_by_node(&codes[0], "".to_string());
+        let service = &analyser.to_container_services()[0];
+
+        assert_eq!(service.demands.len(), 2);
+        assert_eq!(service.demands[0].source_caller, "UserGateway.findUser");
+        assert_eq!(service.demands[0].target_url, "/api/users/{id}");
+        assert_eq!(service.demands[0].target_http_method, "Get");
+        assert_eq!(service.demands[1].target_url, "/api/users");
+        assert_eq!(service.demands[1].target_http_method, "Post");
+    }
+}
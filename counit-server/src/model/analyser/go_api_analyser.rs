@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::model::{CodeCall, CodeDataStruct, CodeFunction, ContainerService, ContainerSupply};
+use crate::model::analyser::{ApiAnalyser, http_method_by_prefix, is_string_literal, join_path, unquote};
+
+/// Imports of the router packages we know, echo is imported with its major version, like
+/// `github.com/labstack/echo/v4`.
+const ROUTER_IMPORTS: [&str; 3] = ["github.com/gin-gonic/gin", "github.com/labstack/echo", "net/http"];
+
+/// Resources of gin, echo and net/http: routes like `r.GET("/users/:id", getUser)`, prefixed by
+/// the router group they are registered on, like `v1 := r.Group("/v1")`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GoApiAnalyser {
+    resources: Vec<ContainerSupply>,
+}
+
+impl ApiAnalyser for GoApiAnalyser {
+    fn analysis_by_node(&mut self, node: &CodeDataStruct, _workspace: String) {
+        let use_router = node.imports.iter()
+            .any(|import| ROUTER_IMPORTS.iter().any(|it| import.source.starts_with(it)));
+        if !use_router {
+            return;
+        }
+
+        for func in &node.functions {
+            let groups = router_groups(func);
+
+            for call in &func.function_calls {
+                let Some((http_method, url, handler)) = route(call) else {
+                    continue;
+                };
+
+                // Chapi keeps the variable of `v1.GET(...)` as origin node name, and the resolved
+                // type as node name
+                let receiver = if call.origin_node_name.is_empty() { &call.node_name } else { &call.origin_node_name };
+                let prefix = groups.get(receiver).cloned().unwrap_or_default();
+
+                self.resources.push(ContainerSupply {
+                    source_url: join_path(&prefix, &url),
+                    source_http_method: http_method,
+                    package_name: node.package.clone(),
+                    class_name: node.node_name.clone(),
+                    method_name: handler.unwrap_or_else(|| func.name.clone()),
+                    produces: vec![],
+                    consumes: vec![],
+                });
+            }
+        }
+    }
+
+    fn to_container_services(&self) -> Vec<ContainerService> {
+        vec![ContainerService {
+            name: "".to_string(),
+            resources: self.resources.clone(),
+            demands: vec![],
+        }]
+    }
+
+    fn new() -> Self {
+        GoApiAnalyser {
+            resources: vec![],
+        }
+    }
+}
+
+/// The http method, url and handler of a route registration:
+///
+/// - gin and echo: `r.GET("/", index)`, `r.Any("/", index)`, `r.Handle("GET", "/", index)`
+/// - net/http: `http.HandleFunc("/", index)`, or with a method since Go 1.22: `mux.HandleFunc("GET /", index)`
+fn route(call: &CodeCall) -> Option<(String, String, Option<String>)> {
+    let params: Vec<&str> = call.parameters.iter().map(|it| it.type_value.as_str()).collect();
+    let handler = |index: usize| params.get(index).map(|it| it.to_string());
+
+    match call.function_name.as_str() {
+        "GET" | "POST" | "PUT" | "DELETE" | "PATCH" | "HEAD" | "OPTIONS" if has_path(&params, 0) => {
+            Some((http_method_by_prefix(&call.function_name)?, unquote(params[0]), handler(1)))
+        }
+        "Any" if has_path(&params, 0) => Some(("Any".to_string(), unquote(params[0]), handler(1))),
+        // gin's `Handle` and echo's `Add` take the method first, as `"GET"` or `http.MethodGet`
+        "Handle" | "Add" if params.len() >= 3 && has_path(&params, 1) => {
+            let method = unquote(params[0]);
+            let method = method.trim_start_matches("http.Method");
+            Some((http_method_by_prefix(method)?, unquote(params[1]), handler(2)))
+        }
+        "Handle" | "HandleFunc" if has_path(&params, 0) => {
+            let pattern = unquote(params[0]);
+            let (http_method, url) = match pattern.split_once(' ') {
+                Some((method, url)) => (http_method_by_prefix(method)?, url.trim().to_string()),
+                None => ("Any".to_string(), pattern),
+            };
+            Some((http_method, url, handler(1)))
+        }
+        _ => None,
+    }
+}
+
+fn has_path(params: &[&str], index: usize) -> bool {
+    params.len() > index && is_string_literal(params[index])
+}
+
+/// The url prefix of each router group variable in the function, Chapi keeps the initializer of
+/// `v1 := r.Group("/v1")` as the default value of the local variable. Nested groups, like
+/// `admin := v1.Group("/admin")`, get the prefix of their parent.
+fn router_groups(func: &CodeFunction) -> HashMap<String, String> {
+    static GROUP_REGEX: OnceLock<Regex> = OnceLock::new();
+    let group_regex = GROUP_REGEX.get_or_init(|| Regex::new(r#"^&?(\w+)\.Group\(\s*["`]([^"`]*)["`]"#).unwrap());
+
+    let mut groups: HashMap<String, String> = HashMap::new();
+    for variable in &func.local_variables {
+        let initializer = variable.default_value.as_deref().unwrap_or(&variable.type_type);
+        if let Some(captures) = group_regex.captures(initializer.trim()) {
+            let parent = groups.get(&captures[1]).cloned().unwrap_or_default();
+            groups.insert(variable.type_value.clone(), join_path(&parent, &captures[2]));
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model::analyser::ApiAnalyser;
+    use crate::model::analyser::go_api_analyser::GoApiAnalyser;
+    use crate::model::CodeDataStruct;
+
+    fn analyse(name: &str) -> Vec<String> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).parent()
+            .unwrap()
+            .join("_fixtures/go/api")
+            .join(name);
+        let nodes: Vec<CodeDataStruct> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        let mut analyser = GoApiAnalyser::new();
+        analyser.analysis(&nodes, "");
+        analyser.to_container_services()[0].resources.iter()
+            .map(|it| format!("{} {} {}", it.source_http_method, it.source_url, it.method_name))
+            .collect()
+    }
+
+    #[test]
+    fn should_analyse_gin_routes_and_groups() {
+        assert_eq!(analyse("gin.json"), vec![
+            "Get /ping ping",
+            "Get /api/v1/users/:id getUser",
+            "Post /api/v1/users createUser",
+            "Delete /api/v1/admin/users/:id deleteUser",
+            "Put /settings updateSettings",
+        ]);
+    }
+
+    #[test]
+    fn should_analyse_echo_routes_and_groups() {
+        assert_eq!(analyse("echo.json"), vec![
+            "Get /users/:id getUser",
+            "Post /admin/users createUser",
+            "Any /health health",
+        ]);
+    }
+
+    #[test]
+    fn should_analyse_net_http_handlers() {
+        assert_eq!(analyse("net_http.json"), vec![
+            "Any /hello hello",
+            "Get /items/{id} getItem",
+            "Any /static fileServer",
+        ]);
+    }
+}
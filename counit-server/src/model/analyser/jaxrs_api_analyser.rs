@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::{CodeAnnotation, CodeDataStruct, ContainerService, ContainerSupply};
+use crate::model::analyser::{annotation_value, ApiAnalyser, http_method_by_prefix, is_string_literal, join_path, unquote};
+
+/// Resources of JAX-RS (Jersey, RESTEasy, Quarkus) classes: `@Path("/users")` on the class, and
+/// `@GET` with an optional `@Path("{id}")` on the methods. The `@Produces` and `@Consumes` of a
+/// method replace the ones of the class.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JaxRsApiAnalyser {
+    resources: Vec<ContainerSupply>,
+}
+
+impl ApiAnalyser for JaxRsApiAnalyser {
+    fn analysis_by_node(&mut self, node: &CodeDataStruct, _workspace: String) {
+        let Some(class_path) = node.annotations.iter().find(|it| simple_name(&it.name) == "Path") else {
+            return;
+        };
+        let base_url = annotation_value(class_path, &["value"]).unwrap_or_default();
+        let class_produces = media_types(&node.annotations, "Produces");
+        let class_consumes = media_types(&node.annotations, "Consumes");
+
+        for func in &node.functions {
+            // methods with only `@Path` are sub-resource locators, they don't serve a request
+            let Some(http_method) = func.annotations.iter().find_map(|it| match simple_name(&it.name) {
+                name @ ("GET" | "POST" | "PUT" | "DELETE" | "PATCH") => http_method_by_prefix(name),
+                _ => None,
+            }) else {
+                continue;
+            };
+
+            let sub_url = func.annotations.iter()
+                .find(|it| simple_name(&it.name) == "Path")
+                .and_then(|it| annotation_value(it, &["value"]))
+                .unwrap_or_default();
+
+            self.resources.push(ContainerSupply {
+                source_url: join_path(&base_url, &sub_url),
+                source_http_method: http_method,
+                package_name: node.package.clone(),
+                class_name: node.node_name.clone(),
+                method_name: func.name.clone(),
+                produces: media_types(&func.annotations, "Produces").unwrap_or_else(|| class_produces.clone().unwrap_or_default()),
+                consumes: media_types(&func.annotations, "Consumes").unwrap_or_else(|| class_consumes.clone().unwrap_or_default()),
+            });
+        }
+    }
+
+    fn to_container_services(&self) -> Vec<ContainerService> {
+        vec![ContainerService {
+            name: "".to_string(),
+            resources: self.resources.clone(),
+            demands: vec![],
+        }]
+    }
+
+    fn new() -> Self {
+        JaxRsApiAnalyser {
+            resources: vec![],
+        }
+    }
+}
+
+/// `javax.ws.rs.GET` and `jakarta.ws.rs.GET` are both `GET`
+fn simple_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// The media types of the `@Produces` or `@Consumes` annotation, none without the annotation.
+/// Both `"application/json"` and `MediaType.APPLICATION_JSON` are `application/json`.
+fn media_types(annotations: &[CodeAnnotation], name: &str) -> Option<Vec<String>> {
+    let annotation = annotations.iter().find(|it| simple_name(&it.name) == name)?;
+    let mut types: Vec<String> = vec![];
+    for kv in &annotation.key_values {
+        for value in kv.value.trim_matches(|c| c == '{' || c == '}').split(',') {
+            let value = value.trim();
+            let media_type = if is_string_literal(value) {
+                unquote(value)
+            } else {
+                media_type_of_constant(simple_name(value))
+            };
+            if !media_type.is_empty() && !types.contains(&media_type) {
+                types.push(media_type);
+            }
+        }
+    }
+
+    Some(types)
+}
+
+/// The value of a `MediaType` constant, like `text/plain` for `TEXT_PLAIN`.
+fn media_type_of_constant(constant: &str) -> String {
+    match constant {
+        "WILDCARD" => "*/*".to_string(),
+        "APPLICATION_FORM_URLENCODED" => "application/x-www-form-urlencoded".to_string(),
+        "SERVER_SENT_EVENTS" => "text/event-stream".to_string(),
+        _ => match constant.split_once('_') {
+            Some((kind, subtype)) => format!("{}/{}", kind.to_lowercase(), subtype.to_lowercase().replace('_', "-")),
+            None => constant.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::analyser::ApiAnalyser;
+    use crate::model::analyser::jaxrs_api_analyser::JaxRsApiAnalyser;
+    use crate::model::CodeDataStruct;
+
+    #[test]
+    fn should_compose_class_and_method_path() {
+        let demo_code = r#"[
+  {
+    "NodeName": "UserResource",
+    "Package": "com.example.user",
+    "Annotations": [
+      { "Name": "Path", "KeyValues": [{ "Key": "\"users\"", "Value": "\"users\"" }] },
+      { "Name": "Produces", "KeyValues": [{ "Key": "MediaType.APPLICATION_JSON", "Value": "MediaType.APPLICATION_JSON" }] }
+    ],
+    "Functions": [
+      {
+        "Name": "list",
+        "Annotations": [{ "Name": "GET" }]
+      },
+      {
+        "Name": "get",
+        "Annotations": [
+          { "Name": "GET" },
+          { "Name": "Path", "KeyValues": [{ "Key": "\"{id}\"", "Value": "\"{id}\"" }] }
+        ]
+      },
+      {
+        "Name": "create",
+        "Annotations": [
+          { "Name": "jakarta.ws.rs.POST" },
+          { "Name": "Consumes", "KeyValues": [{ "Key": "{MediaType.APPLICATION_XML, \"application/json\"}", "Value": "{MediaType.APPLICATION_XML, \"application/json\"}" }] },
+          { "Name": "Produces", "KeyValues": [{ "Key": "\"text/plain\"", "Value": "\"text/plain\"" }] }
+        ]
+      },
+      {
+        "Name": "orders",
+        "Annotations": [{ "Name": "Path", "KeyValues": [{ "Key": "\"{id}/orders\"", "Value": "\"{id}/orders\"" }] }]
+      }
+    ]
+  }
+]"#;
+
+        let mut analyser = JaxRsApiAnalyser::new();
+        let codes: Vec<CodeDataStruct> = serde_json::from_str(demo_code).unwrap();
+        analyser.analysis_by_node(&codes[0], "".to_string());
+
+        let apis: Vec<String> = analyser.to_container_services()[0].resources.iter()
+            .map(|it| format!("{} {} {}", it.source_http_method, it.source_url, it.method_name))
+            .collect();
+        assert_eq!(apis, vec!["Get /users list", "Get /users/{id} get", "Post /users create"]);
+
+        let media_types: Vec<String> = analyser.to_container_services()[0].resources.iter()
+            .map(|it| format!("{} {:?} {:?}", it.method_name, it.produces, it.consumes))
+            .collect();
+        assert_eq!(media_types, vec![
+            r#"list ["application/json"] []"#,
+            r#"get ["application/json"] []"#,
+            r#"create ["text/plain"] ["application/xml", "application/json"]"#,
+        ]);
+    }
+}
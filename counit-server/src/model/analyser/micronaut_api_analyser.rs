@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::{CodeDataStruct, ContainerService, ContainerSupply};
+use crate::model::analyser::{annotation_value, ApiAnalyser, http_method_by_prefix, join_path};
+
+/// Resources of Micronaut controllers: `@Controller("/hello")` on the class, and `@Get("/{name}")`
+/// on the methods.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MicronautApiAnalyser {
+    resources: Vec<ContainerSupply>,
+}
+
+impl ApiAnalyser for MicronautApiAnalyser {
+    fn analysis_by_node(&mut self, node: &CodeDataStruct, _workspace: String) {
+        let controller = node.filter_annotations(vec!["Controller"]);
+        let Some(controller) = controller.first() else {
+            return;
+        };
+        let base_url = annotation_value(controller, &["value"]).unwrap_or_default();
+
+        for func in &node.functions {
+            // Spring also has a `@Controller`, but its methods use `@GetMapping`, so they don't match
+            let mapping = func.annotations.iter().find_map(|it| match &it.name[..] {
+                "Get" | "Post" | "Put" | "Delete" | "Patch" => {
+                    let url = annotation_value(it, &["value", "uri"]).unwrap_or_default();
+                    http_method_by_prefix(&it.name).map(|method| (method, url))
+                }
+                _ => None,
+            });
+
+            if let Some((http_method, sub_url)) = mapping {
+                self.resources.push(ContainerSupply {
+                    source_url: join_path(&base_url, &sub_url),
+                    source_http_method: http_method,
+                    package_name: node.package.clone(),
+                    class_name: node.node_name.clone(),
+                    method_name: func.name.clone(),
+                    produces: vec![],
+                    consumes: vec![],
+                });
+            }
+        }
+    }
+
+    fn to_container_services(&self) -> Vec<ContainerService> {
+        vec![ContainerService {
+            name: "".to_string(),
+            resources: self.resources.clone(),
+            demands: vec![],
+        }]
+    }
+
+    fn new() -> Self {
+        MicronautApiAnalyser {
+            resources: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::analyser::ApiAnalyser;
+    use crate::model::analyser::micronaut_api_analyser::MicronautApiAnalyser;
+    use crate::model::CodeDataStruct;
+
+    #[test]
+    fn should_compose_controller_and_method_path() {
+        let demo_code = r#"[
+  {
+    "NodeName": "HelloController",
+    "Package": "com.example",
+    "Annotations": [
+      { "Name": "Controller", "KeyValues": [{ "Key": "\"/hello\"", "Value": "\"/hello\"" }] }
+    ],
+    "Functions": [
+      {
+        "Name": "index",
+        "Annotations": [{ "Name": "Get" }]
+      },
+      {
+        "Name": "greet",
+        "Annotations": [{ "Name": "Get", "KeyValues": [{ "Key": "uri", "Value": "\"/{name}\"" }] }]
+      },
+      {
+        "Name": "save",
+        "Annotations": [{ "Name": "Post", "KeyValues": [{ "Key": "\"/\"", "Value": "\"/\"" }] }]
+      },
+      {
+        "Name": "legacy",
+        "Annotations": [{ "Name": "GetMapping", "KeyValues": [{ "Key": "\"/legacy\"", "Value": "\"/legacy\"" }] }]
+      }
+    ]
+  }
+]"#;
+
+        let mut analyser = MicronautApiAnalyser::new();
+        let codes: Vec<CodeDataStruct> = serde_json::from_str(demo_code).unwrap();
+        analyser.analysis_by_node(&codes[0], "".to_string());
+
+        let apis: Vec<String> = analyser.to_container_services()[0].resources.iter()
+            .map(|it| format!("{} {}", it.source_http_method, it.source_url))
+            .collect();
+        assert_eq!(apis, vec!["Get /hello", "Get /hello/{name}", "Post /hello"]);
+    }
+}
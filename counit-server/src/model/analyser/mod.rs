@@ -1,10 +1,161 @@
-use crate::model::{CodeDataStruct, ContainerService};
+use crate::model::{CodeAnnotation, CodeDataStruct, ContainerService};
 
 pub mod api_analyser;
+pub mod jaxrs_api_analyser;
+pub mod micronaut_api_analyser;
+pub mod go_api_analyser;
+pub mod typescript_api_analyser;
+pub mod spring_openapi;
 
+use api_analyser::JavaApiAnalyser;
+use jaxrs_api_analyser::JaxRsApiAnalyser;
+use micronaut_api_analyser::MicronautApiAnalyser;
+use go_api_analyser::GoApiAnalyser;
+use typescript_api_analyser::TypeScriptApiAnalyser;
 
-trait ApiAnalyser {
+pub trait ApiAnalyser {
     fn analysis_by_node(&mut self, node: &CodeDataStruct, workspace: String);
     fn to_container_services(&self) -> Vec<ContainerService>;
     fn new() -> Self;
+
+    /// Analyse all nodes, including their inner structures, e.g. Java inner classes.
+    fn analysis(&mut self, nodes: &[CodeDataStruct], workspace: &str) {
+        for node in nodes {
+            self.analysis_by_node(node, workspace.to_string());
+            self.analysis(&node.inner_structures, workspace);
+        }
+    }
+}
+
+/// Run the api analysers of `language` over the Chapi nodes, returns `None` if the language is not
+/// supported yet.
+pub fn analyse_container_services(
+    language: &str,
+    nodes: &[CodeDataStruct],
+    workspace: &str,
+) -> Option<Vec<ContainerService>> {
+    match language.to_lowercase().as_str() {
+        "java" | "kotlin" => Some(merge_services(vec![
+            run_analyser::<JavaApiAnalyser>(nodes, workspace),
+            run_analyser::<JaxRsApiAnalyser>(nodes, workspace),
+            run_analyser::<MicronautApiAnalyser>(nodes, workspace),
+        ])),
+        "go" | "golang" => Some(run_analyser::<GoApiAnalyser>(nodes, workspace)),
+        "typescript" | "javascript" | "ts" | "js" => Some(run_analyser::<TypeScriptApiAnalyser>(nodes, workspace)),
+        _ => None,
+    }
+}
+
+fn run_analyser<T: ApiAnalyser>(nodes: &[CodeDataStruct], workspace: &str) -> Vec<ContainerService> {
+    let mut analyser = T::new();
+    analyser.analysis(nodes, workspace);
+    analyser.to_container_services()
+}
+
+/// The analysers of one language all look at the same codebase, so their results belong to a
+/// single service.
+fn merge_services(results: Vec<Vec<ContainerService>>) -> Vec<ContainerService> {
+    let mut merged = ContainerService {
+        name: "".to_string(),
+        demands: vec![],
+        resources: vec![],
+    };
+
+    for service in results.into_iter().flatten() {
+        merged.demands.extend(service.demands);
+        merged.resources.extend(service.resources);
+    }
+
+    vec![merged]
+}
+
+/// The value of the first attribute named by `keys`, or of the unnamed attribute: for
+/// `@GetMapping("/")` Chapi uses the value as key.
+pub(crate) fn annotation_value(annotation: &CodeAnnotation, keys: &[&str]) -> Option<String> {
+    annotation.key_values.iter()
+        .find(|kv| keys.contains(&kv.key.as_str()) || kv.key == kv.value)
+        .map(|kv| unquote(&kv.value))
+}
+
+/// Strip the quotes of a string literal, TypeScript and Go also use `'` and `` ` ``.
+pub(crate) fn unquote(value: &str) -> String {
+    value.trim_matches(|c| c == '"' || c == '\'' || c == '`').to_string()
+}
+
+pub(crate) fn is_string_literal(value: &str) -> bool {
+    value.starts_with('"') || value.starts_with('\'') || value.starts_with('`')
+}
+
+/// The http method of a call or annotation like `getForObject`, `post`, `DELETE` or `Get`.
+pub(crate) fn http_method_by_prefix(function_name: &str) -> Option<String> {
+    let lowercase = function_name.to_lowercase();
+    ["get", "post", "delete", "put", "patch", "head", "options"].iter()
+        .find(|method| lowercase.starts_with(*method))
+        .map(|method| format!("{}{}", method[..1].to_uppercase(), &method[1..]))
+}
+
+pub(crate) fn join_url(base_url: &str, sub_url: &str) -> String {
+    let mut route = format!("{}{}", base_url, sub_url);
+    if !route.starts_with('/') {
+        route = format!("/{}", route);
+    }
+
+    route.replace("//", "/")
+}
+
+/// Join paths which may or may not start or end with a slash, like `@Path("users")` and
+/// `@Path("{id}")` in JAX-RS.
+pub(crate) fn join_path(base_url: &str, sub_url: &str) -> String {
+    let segments = base_url.split('/')
+        .chain(sub_url.split('/'))
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    format!("/{}", segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model::analyser::analyse_container_services;
+    use crate::model::CodeDataStruct;
+
+    fn fixture(name: &str) -> Vec<CodeDataStruct> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).parent()
+            .unwrap()
+            .join("_fixtures")
+            .join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn should_analyse_spring_controller() {
+        let nodes = fixture("java/api/0.json");
+        let services = analyse_container_services("Java", &nodes, "").unwrap();
+
+        let apis: Vec<String> = services[0].resources.iter()
+            .map(|it| format!("{} {}", it.source_http_method, it.source_url))
+            .collect();
+        assert_eq!(apis, vec!["Get /api/blogs", "Post /api/blogs", "Get /api/blogs/{id}"]);
+    }
+
+    #[test]
+    fn should_analyse_inner_structures() {
+        let mut nodes = fixture("java/api/0.json");
+        let mut outer = nodes[0].clone();
+        outer.node_name = "BlogApplication".to_string();
+        outer.annotations = vec![];
+        outer.functions = vec![];
+        outer.inner_structures = vec![nodes.remove(0)];
+
+        let services = analyse_container_services("java", &[outer], "").unwrap();
+        assert_eq!(services[0].resources.len(), 3);
+        assert_eq!(services[0].resources[0].class_name, "BlogController");
+    }
+
+    #[test]
+    fn should_skip_unsupported_language() {
+        assert!(analyse_container_services("cobol", &[], "").is_none());
+    }
 }
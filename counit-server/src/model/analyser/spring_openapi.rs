@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use tracing::warn;
+
+use crate::model::{CodeAnnotation, CodeDataStruct, CodeField, CodeFunction, CodeProperty, DataStructType};
+use crate::model::analyser::{annotation_value, join_url, unquote};
+use crate::model::analyser::api_analyser::spring_mapping;
+use crate::model::openapi_document::{
+    ApiResponse, Components, Info, MediaType, OpenApiDocument, Operation, Parameter, RequestBody, Schema,
+};
+
+/// Generate the OpenAPI 3 document of the Spring controllers in `nodes`, the DTO classes in
+/// `nodes` which are used as request or response bodies become component schemas.
+pub fn generate_spring_openapi(title: &str, nodes: &[CodeDataStruct]) -> OpenApiDocument {
+    let mut all_nodes = vec![];
+    flatten(nodes, &mut all_nodes);
+
+    let mut generator = SpringOpenApiGenerator {
+        classes: all_nodes.iter().map(|it| (it.node_name.as_str(), *it)).collect(),
+        schemas: BTreeMap::new(),
+        operation_ids: HashSet::new(),
+    };
+
+    let mut paths: BTreeMap<String, BTreeMap<String, Operation>> = BTreeMap::new();
+    for node in &all_nodes {
+        let is_controller = !node.filter_annotations(vec!["RestController", "Controller", "RequestMapping"]).is_empty();
+        let is_feign = !node.filter_annotations(vec!["FeignClient"]).is_empty();
+        if !is_controller || is_feign {
+            continue;
+        }
+
+        let base_url = node.filter_annotations(vec!["RequestMapping"]).first()
+            .and_then(|it| annotation_value(it, &["value", "path"]))
+            .unwrap_or_default();
+
+        for func in &node.functions {
+            let Some((http_method, sub_url)) = func.annotations.iter().find_map(spring_mapping) else {
+                continue;
+            };
+
+            let path = openapi_path(&join_url(&base_url, &sub_url));
+            let operation = generator.operation(node, func, &path);
+            let operation_id = operation.operation_id.clone();
+            if let Some(previous) = paths.entry(path.clone()).or_default().insert(http_method.to_lowercase(), operation) {
+                warn!(
+                    "`{} {}` is mapped by both `{}` and `{}`, only the last one is kept",
+                    http_method.to_uppercase(), path, previous.operation_id, operation_id
+                );
+            }
+        }
+    }
+
+    OpenApiDocument {
+        openapi: "3.0.3".to_string(),
+        info: Info {
+            title: title.to_string(),
+            version: "1.0.0".to_string(),
+        },
+        paths,
+        components: Components { schemas: generator.schemas },
+    }
+}
+
+fn flatten<'a>(nodes: &'a [CodeDataStruct], output: &mut Vec<&'a CodeDataStruct>) {
+    for node in nodes {
+        output.push(node);
+        flatten(&node.inner_structures, output);
+    }
+}
+
+/// Spring allows a regex in path variables, like `/{id:\d+}`, OpenAPI only takes the name.
+fn openapi_path(url: &str) -> String {
+    let mut path = String::new();
+    let mut depth = 0;
+    let mut in_regex = false;
+    for c in url.chars() {
+        match c {
+            '{' => {
+                depth += 1;
+                if !in_regex {
+                    path.push(c);
+                }
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    in_regex = false;
+                }
+                if !in_regex {
+                    path.push(c);
+                }
+            }
+            ':' if depth == 1 => in_regex = true,
+            _ if in_regex => {}
+            _ => path.push(c),
+        }
+    }
+
+    path
+}
+
+/// Wrappers which don't change the shape of the payload.
+const WRAPPER_TYPES: [&str; 8] = [
+    "ResponseEntity", "HttpEntity", "Optional", "Mono", "CompletableFuture", "Callable", "DeferredResult", "Future",
+];
+const COLLECTION_TYPES: [&str; 9] = [
+    "List", "ArrayList", "LinkedList", "Set", "HashSet", "Collection", "Iterable", "Flux", "Stream",
+];
+const MAP_TYPES: [&str; 6] = ["Map", "HashMap", "LinkedHashMap", "TreeMap", "ConcurrentHashMap", "MultiValueMap"];
+
+struct SpringOpenApiGenerator<'a> {
+    classes: HashMap<&'a str, &'a CodeDataStruct>,
+    schemas: BTreeMap<String, Schema>,
+    operation_ids: HashSet<String>,
+}
+
+impl<'a> SpringOpenApiGenerator<'a> {
+    fn operation(&mut self, node: &CodeDataStruct, func: &CodeFunction, path: &str) -> Operation {
+        let mut operation_id = func.name.clone();
+        let mut index = 1;
+        while self.operation_ids.contains(&operation_id) {
+            operation_id = format!("{}_{}", func.name, index);
+            index += 1;
+        }
+        self.operation_ids.insert(operation_id.clone());
+
+        let summary = func.annotations.iter().find_map(|it| match &it.name[..] {
+            // Swagger 2 `@ApiOperation("...")` and OpenAPI 3 `@Operation(summary = "...")`
+            "ApiOperation" => annotation_value(it, &["value"]),
+            "Operation" => named_value(it, "summary"),
+            _ => None,
+        });
+
+        let path_variables = path_variables(path);
+        let mut parameters = vec![];
+        let mut request_body = None;
+        let mut form_data = Schema::of_type("object", None);
+
+        for param in &func.parameters {
+            let binding = param.annotations.iter().find(|it| {
+                matches!(&it.name[..], "PathVariable" | "RequestParam" | "RequestHeader" | "CookieValue" | "RequestBody" | "RequestPart")
+            });
+
+            let Some(binding) = binding else {
+                // without annotation, Spring binds simple types by name, from the path or the query
+                if is_file(param) {
+                    form_data.properties.insert(param.type_value.clone(), self.schema_of(&param.type_type));
+                } else if is_simple_type(&param.type_type) {
+                    let in_path = path_variables.contains(&param.type_value);
+                    parameters.push(Parameter {
+                        name: param.type_value.clone(),
+                        location: if in_path { "path" } else { "query" }.to_string(),
+                        required: in_path,
+                        schema: self.schema_of(&param.type_type),
+                    });
+                }
+                continue;
+            };
+
+            let name = annotation_value(binding, &["value", "name"]).unwrap_or(param.type_value.clone());
+            let required = named_value(binding, "required").as_deref() != Some("false")
+                && named_value(binding, "defaultValue").is_none();
+
+            match &binding.name[..] {
+                "RequestBody" => {
+                    request_body = Some(RequestBody {
+                        required,
+                        content: BTreeMap::from([(
+                            "application/json".to_string(),
+                            MediaType { schema: self.schema_of(&param.type_type) },
+                        )]),
+                    });
+                }
+                // files are uploaded as multipart form data
+                "RequestPart" | "RequestParam" if is_file(param) || binding.name == "RequestPart" => {
+                    form_data.properties.insert(name.clone(), self.schema_of(&param.type_type));
+                    if required {
+                        form_data.required.push(name);
+                    }
+                }
+                _ => {
+                    let location = match &binding.name[..] {
+                        "PathVariable" => "path",
+                        "RequestHeader" => "header",
+                        "CookieValue" => "cookie",
+                        _ => "query",
+                    };
+                    parameters.push(Parameter {
+                        name,
+                        location: location.to_string(),
+                        // path parameters are always required in OpenAPI
+                        required: required || location == "path",
+                        schema: self.schema_of(&param.type_type),
+                    });
+                }
+            }
+        }
+
+        // a path variable must be declared, even if we don't know where it is bound
+        for variable in path_variables {
+            if !parameters.iter().any(|it| it.location == "path" && it.name == variable) {
+                parameters.push(Parameter {
+                    name: variable,
+                    location: "path".to_string(),
+                    required: true,
+                    schema: Schema::of_type("string", None),
+                });
+            }
+        }
+
+        if request_body.is_none() && !form_data.properties.is_empty() {
+            request_body = Some(RequestBody {
+                required: !form_data.required.is_empty(),
+                content: BTreeMap::from([("multipart/form-data".to_string(), MediaType { schema: form_data })]),
+            });
+        }
+
+        let mut content = BTreeMap::new();
+        let return_type = unwrap_wrappers(&func.return_type);
+        if !return_type.is_empty() && return_type != "void" && return_type != "Void" {
+            content.insert("application/json".to_string(), MediaType { schema: self.schema_of(return_type) });
+        }
+
+        Operation {
+            tags: vec![node.node_name.clone()],
+            operation_id,
+            summary,
+            parameters,
+            request_body,
+            responses: BTreeMap::from([("200".to_string(), ApiResponse { description: "OK".to_string(), content })]),
+        }
+    }
+
+    /// The schema of a Java type, known classes are added to the components and referenced. The
+    /// classes of the project come first, a `Page` or `SiteMap` DTO is not a collection.
+    fn schema_of(&mut self, type_name: &str) -> Schema {
+        let type_name = unwrap_wrappers(type_name);
+        if let Some(element) = type_name.strip_suffix("[]") {
+            return match element {
+                "byte" => Schema::of_type("string", Some("byte")),
+                _ => Schema::array(self.schema_of(element)),
+            };
+        }
+
+        let (raw, arguments) = split_generic(type_name);
+        let simple_name = raw.rsplit('.').next().unwrap_or(raw);
+
+        if let Some(node) = self.classes.get(simple_name).copied() {
+            self.register(node);
+            return Schema::reference(simple_name);
+        }
+
+        if COLLECTION_TYPES.contains(&simple_name) || simple_name == "Page" {
+            let items = arguments.first().map_or(Schema::of_type("object", None), |it| self.schema_of(it));
+            return Schema::array(items);
+        }
+        if MAP_TYPES.contains(&simple_name) {
+            let mut schema = Schema::of_type("object", None);
+            if let Some(value) = arguments.get(1) {
+                let value = self.schema_of(value);
+                // a `MultiValueMap<K, V>` is a `Map<K, List<V>>`
+                let value = if simple_name == "MultiValueMap" { Schema::array(value) } else { value };
+                schema.additional_properties = Some(Box::new(value));
+            }
+            return schema;
+        }
+
+        primitive_schema(simple_name).unwrap_or(Schema::of_type("object", None))
+    }
+
+    fn register(&mut self, node: &'a CodeDataStruct) {
+        if self.schemas.contains_key(&node.node_name) {
+            return;
+        }
+        // the constants of an enum are its fields without modifiers, like `ACTIVE`
+        if matches!(node.data_type, DataStructType::Enum) {
+            let mut schema = Schema::of_type("string", None);
+            schema.enum_values = node.fields.iter()
+                .filter(|it| it.modifiers.is_empty())
+                .filter_map(|it| it.type_key.clone())
+                .collect();
+            self.schemas.insert(node.node_name.clone(), schema);
+            return;
+        }
+
+        // a placeholder for self-referencing classes, like a tree node
+        self.schemas.insert(node.node_name.clone(), Schema::default());
+
+        let mut schema = Schema::of_type("object", None);
+        for field in self.fields_of(node) {
+            let (Some(name), Some(type_type)) = (&field.type_key, &field.type_type) else {
+                continue;
+            };
+            if field.modifiers.iter().any(|it| it == "static") {
+                continue;
+            }
+
+            schema.properties.insert(name.clone(), self.schema_of(type_type));
+            let is_required = field.annotations.iter()
+                .any(|it| matches!(&it.name[..], "NotNull" | "NotBlank" | "NotEmpty"));
+            if is_required {
+                schema.required.push(name.clone());
+            }
+        }
+
+        self.schemas.insert(node.node_name.clone(), schema);
+    }
+
+    /// Fields of the class and of its known super classes.
+    fn fields_of(&self, node: &'a CodeDataStruct) -> Vec<&'a CodeField> {
+        let mut fields: Vec<&CodeField> = node.fields.iter().collect();
+        let mut parent = self.classes.get(node.extend.as_str()).copied();
+        let mut visited = HashSet::from([node.node_name.as_str()]);
+        while let Some(class) = parent {
+            if !visited.insert(class.node_name.as_str()) {
+                break;
+            }
+            fields.extend(class.fields.iter());
+            parent = self.classes.get(class.extend.as_str()).copied();
+        }
+
+        fields
+    }
+}
+
+/// The value of an attribute by its name only, unlike [annotation_value] which also takes the
+/// unnamed attribute.
+fn named_value(annotation: &CodeAnnotation, key: &str) -> Option<String> {
+    annotation.key_values.iter()
+        .find(|kv| kv.key == key)
+        .map(|kv| unquote(&kv.value))
+}
+
+fn path_variables(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|it| it.to_string())
+        .collect()
+}
+
+fn is_file(param: &CodeProperty) -> bool {
+    param.type_type.contains("MultipartFile") || param.type_type.ends_with("FilePart")
+}
+
+/// `ResponseEntity<List<Blog>>` -> `List<Blog>`
+fn unwrap_wrappers(type_name: &str) -> &str {
+    let mut type_name = type_name.trim();
+    loop {
+        let (raw, arguments) = split_generic(type_name);
+        let simple_name = raw.rsplit('.').next().unwrap_or(raw);
+        match arguments.first() {
+            Some(argument) if WRAPPER_TYPES.contains(&simple_name) => type_name = argument,
+            _ => return type_name,
+        }
+    }
+}
+
+/// `Map<String, List<Blog>>` -> (`Map`, [`String`, `List<Blog>`])
+fn split_generic(type_name: &str) -> (&str, Vec<&str>) {
+    let Some(start) = type_name.find('<') else {
+        return (type_name, vec![]);
+    };
+    let Some(end) = type_name.rfind('>') else {
+        return (type_name, vec![]);
+    };
+
+    let inner = &type_name[start + 1..end];
+    let mut arguments = vec![];
+    let mut depth = 0;
+    let mut from = 0;
+    for (index, c) in inner.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => depth -= 1,
+            ',' if depth == 0 => {
+                arguments.push(inner[from..index].trim());
+                from = index + 1;
+            }
+            _ => {}
+        }
+    }
+    arguments.push(inner[from..].trim());
+
+    (type_name[..start].trim(), arguments.into_iter().filter(|it| !it.is_empty()).collect())
+}
+
+fn is_simple_type(type_name: &str) -> bool {
+    let simple_name = type_name.rsplit('.').next().unwrap_or(type_name);
+    primitive_schema(simple_name).is_some_and(|it| it.schema_type.as_deref() != Some("object"))
+}
+
+fn primitive_schema(simple_name: &str) -> Option<Schema> {
+    let schema = match simple_name {
+        "String" | "CharSequence" | "char" | "Character" => Schema::of_type("string", None),
+        "int" | "Integer" | "short" | "Short" | "byte" | "Byte" => Schema::of_type("integer", Some("int32")),
+        "long" | "Long" | "BigInteger" => Schema::of_type("integer", Some("int64")),
+        "float" | "Float" => Schema::of_type("number", Some("float")),
+        "double" | "Double" | "BigDecimal" => Schema::of_type("number", Some("double")),
+        "boolean" | "Boolean" => Schema::of_type("boolean", None),
+        "LocalDate" => Schema::of_type("string", Some("date")),
+        "Date" | "LocalDateTime" | "OffsetDateTime" | "ZonedDateTime" | "Instant" => Schema::of_type("string", Some("date-time")),
+        "UUID" => Schema::of_type("string", Some("uuid")),
+        "MultipartFile" | "FilePart" | "Resource" => Schema::of_type("string", Some("binary")),
+        "Object" | "?" | "JsonNode" => Schema::of_type("object", None),
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use crate::model::analyser::spring_openapi::{generate_spring_openapi, openapi_path};
+    use crate::model::CodeDataStruct;
+
+    fn fixture(name: &str) -> Vec<CodeDataStruct> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).parent()
+            .unwrap()
+            .join("_fixtures")
+            .join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn should_generate_paths_of_controller() {
+        let document = generate_spring_openapi("blog", &fixture("java/api/0.json"));
+
+        let paths: Vec<&String> = document.paths.keys().collect();
+        assert_eq!(paths, vec!["/api/blogs", "/api/blogs/{id}"]);
+
+        let get_blog = &document.paths["/api/blogs/{id}"]["get"];
+        assert_eq!(get_blog.operation_id, "getBlogById");
+        assert_eq!(get_blog.summary.as_deref(), Some("获取指定ID的博客"));
+        assert_eq!(get_blog.parameters[0].location, "path");
+        assert_eq!(get_blog.parameters[0].schema.format.as_deref(), Some("int64"));
+    }
+
+    #[test]
+    fn should_map_parameters_and_schemas() {
+        let document = generate_spring_openapi("user", &fixture("java/openapi/user_controller.json"));
+        let json = serde_json::to_value(&document).unwrap();
+
+        let get_user = &json["paths"]["/users/{id}"]["get"];
+        assert_eq!(get_user["parameters"][0]["name"], "id");
+        assert_eq!(get_user["parameters"][0]["in"], "path");
+        assert_eq!(get_user["parameters"][1]["in"], "header");
+        assert_eq!(get_user["responses"]["200"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/UserDto");
+
+        let search = &json["paths"]["/users"]["get"];
+        assert_eq!(search["parameters"][0]["in"], "query");
+        assert_eq!(search["parameters"][0]["required"], false);
+        assert_eq!(search["responses"]["200"]["content"]["application/json"]["schema"]["items"]["$ref"], "#/components/schemas/UserDto");
+
+        let create = &json["paths"]["/users"]["post"];
+        assert_eq!(create["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/CreateUserRequest");
+
+        let upload = &json["paths"]["/users/{id}/avatar"]["put"];
+        assert_eq!(upload["requestBody"]["content"]["multipart/form-data"]["schema"]["properties"]["file"]["format"], "binary");
+
+        let user = &json["components"]["schemas"]["UserDto"];
+        assert_eq!(user["properties"]["id"]["type"], "integer");
+        assert_eq!(user["properties"]["address"]["$ref"], "#/components/schemas/Address");
+        assert_eq!(user["properties"]["tags"]["type"], "array");
+        assert_eq!(user["properties"]["createdBy"]["type"], "string");
+        assert!(user["properties"]["serialVersionUID"].is_null());
+        assert_eq!(user["properties"]["status"]["$ref"], "#/components/schemas/UserStatus");
+        assert_eq!(user["properties"]["attributes"]["additionalProperties"]["items"]["type"], "string");
+        assert_eq!(user["properties"]["siteMap"]["$ref"], "#/components/schemas/SiteMap");
+
+        let status = &json["components"]["schemas"]["UserStatus"];
+        assert_eq!(status["type"], "string");
+        assert_eq!(status["enum"], json!(["ACTIVE", "BLOCKED"]));
+
+        let request = &json["components"]["schemas"]["CreateUserRequest"];
+        assert_eq!(request["required"][0], "name");
+    }
+
+    #[test]
+    fn should_strip_regex_of_path_variables() {
+        assert_eq!(openapi_path("/users/{id:\\d+}/posts/{postId}"), "/users/{id}/posts/{postId}");
+    }
+}
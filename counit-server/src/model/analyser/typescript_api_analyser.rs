@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::{CodeCall, CodeDataStruct, ContainerService, ContainerSupply};
+use crate::model::analyser::{annotation_value, ApiAnalyser, http_method_by_prefix, is_string_literal, join_path, unquote};
+
+/// A route like `router.get('/:id', getUser)`, its prefix is only known after every file is seen.
+#[derive(Serialize, Deserialize, Clone)]
+struct ExpressRoute {
+    file: String,
+    receiver: String,
+    http_method: String,
+    url: String,
+    package_name: String,
+    class_name: String,
+    method_name: String,
+}
+
+/// A router mounted with `app.use('/api/users', usersRouter)`.
+#[derive(Serialize, Deserialize, Clone)]
+struct ExpressMount {
+    file: String,
+    receiver: String,
+    prefix: String,
+    router: String,
+}
+
+/// Resources of NestJS controllers, `@Controller('cats')` with `@Get(':id')`, and of Express
+/// routes, `app.get('/cats/:id', handler)`.
+///
+/// Express routers are often declared in their own file and mounted in another one, like
+/// `app.use('/api/users', usersRouter)`, so the imports and exports of each file are kept to
+/// resolve the prefix of a route.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TypeScriptApiAnalyser {
+    resources: Vec<ContainerSupply>,
+    routes: Vec<ExpressRoute>,
+    mounts: Vec<ExpressMount>,
+    /// (file, local name, import source)
+    imports: Vec<(String, String, String)>,
+    /// (file, exported name)
+    exports: Vec<(String, String)>,
+}
+
+impl ApiAnalyser for TypeScriptApiAnalyser {
+    fn analysis_by_node(&mut self, node: &CodeDataStruct, _workspace: String) {
+        self.analysis_nest_controller(node);
+
+        let use_express = node.imports.iter().any(|it| it.source == "express");
+        if use_express {
+            self.analysis_express(node);
+        }
+    }
+
+    fn to_container_services(&self) -> Vec<ContainerService> {
+        let mut resources = self.resources.clone();
+        for route in &self.routes {
+            let prefix = self.prefix_of(&route.file, &route.receiver, 0);
+            resources.push(ContainerSupply {
+                source_url: join_path(&prefix, &route.url),
+                source_http_method: route.http_method.clone(),
+                package_name: route.package_name.clone(),
+                class_name: route.class_name.clone(),
+                method_name: route.method_name.clone(),
+                produces: vec![],
+                consumes: vec![],
+            });
+        }
+
+        vec![ContainerService {
+            name: "".to_string(),
+            resources,
+            demands: vec![],
+        }]
+    }
+
+    fn new() -> Self {
+        TypeScriptApiAnalyser {
+            resources: vec![],
+            routes: vec![],
+            mounts: vec![],
+            imports: vec![],
+            exports: vec![],
+        }
+    }
+}
+
+/// Nested mounts deeper than this are treated as a cycle.
+const MAX_MOUNT_DEPTH: usize = 8;
+
+impl TypeScriptApiAnalyser {
+    fn analysis_nest_controller(&mut self, node: &CodeDataStruct) {
+        let controller = node.filter_annotations(vec!["Controller"]);
+        let Some(controller) = controller.first() else {
+            return;
+        };
+        let base_url = annotation_value(controller, &["path"]).unwrap_or_default();
+
+        for func in &node.functions {
+            let mapping = func.annotations.iter().find_map(|it| match &it.name[..] {
+                "Get" | "Post" | "Put" | "Delete" | "Patch" | "Head" | "Options" => {
+                    let url = annotation_value(it, &["path"]).unwrap_or_default();
+                    http_method_by_prefix(&it.name).map(|method| (method, url))
+                }
+                "All" => Some(("Any".to_string(), annotation_value(it, &["path"]).unwrap_or_default())),
+                _ => None,
+            });
+
+            if let Some((http_method, sub_url)) = mapping {
+                self.resources.push(ContainerSupply {
+                    source_url: join_path(&base_url, &sub_url),
+                    source_http_method: http_method,
+                    package_name: node.package.clone(),
+                    class_name: node.node_name.clone(),
+                    method_name: func.name.clone(),
+                    produces: vec![],
+                    consumes: vec![],
+                });
+            }
+        }
+    }
+
+    fn analysis_express(&mut self, node: &CodeDataStruct) {
+        for import in &node.imports {
+            for name in import.usage_name.iter().chain(Some(&import.as_name)).filter(|it| !it.is_empty()) {
+                self.imports.push((node.file_path.clone(), name.clone(), import.source.clone()));
+            }
+        }
+        for export in &node.exports {
+            self.exports.push((node.file_path.clone(), export.name.clone()));
+        }
+
+        // top level statements are kept in the node or its fields, handlers in functions
+        let top_level = node.function_calls.iter()
+            .chain(node.fields.iter().flat_map(|it| it.calls.iter()))
+            .map(|call| (call, ""));
+        let in_functions = node.functions.iter()
+            .flat_map(|func| func.function_calls.iter().map(|call| (call, func.name.as_str())));
+
+        for (call, function_name) in top_level.chain(in_functions) {
+            let params: Vec<&str> = call.parameters.iter().map(|it| it.type_value.as_str()).collect();
+            // `app.get('env')` reads a setting, a route always has a path and a handler
+            if params.len() < 2 || !is_string_literal(params[0]) {
+                continue;
+            }
+
+            let receiver = receiver(call);
+            let http_method = match call.function_name.as_str() {
+                "get" | "post" | "put" | "delete" | "patch" | "head" | "options" => http_method_by_prefix(&call.function_name),
+                "all" => Some("Any".to_string()),
+                // the router comes after the middlewares: `app.use('/admin', auth, adminRouter)`
+                "use" => {
+                    self.mounts.push(ExpressMount {
+                        file: node.file_path.clone(),
+                        receiver: receiver.to_string(),
+                        prefix: unquote(params[0]),
+                        router: params[params.len() - 1].to_string(),
+                    });
+                    None
+                }
+                _ => None,
+            };
+
+            if let Some(http_method) = http_method {
+                let handler = params[params.len() - 1];
+                let is_named = handler.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.');
+                self.routes.push(ExpressRoute {
+                    file: node.file_path.clone(),
+                    receiver: receiver.to_string(),
+                    http_method,
+                    url: unquote(params[0]),
+                    package_name: node.package.clone(),
+                    class_name: node.node_name.clone(),
+                    method_name: if is_named { handler.to_string() } else { function_name.to_string() },
+                });
+            }
+        }
+    }
+
+    /// The prefix of the routes of `receiver` in `file`, from the mount in the same file, or from
+    /// a mount in a file which imports the exported router.
+    fn prefix_of(&self, file: &str, receiver: &str, depth: usize) -> String {
+        if depth > MAX_MOUNT_DEPTH {
+            return "".to_string();
+        }
+
+        let same_file = self.mounts.iter()
+            .find(|mount| mount.file == file && mount.router == receiver);
+        if let Some(mount) = same_file {
+            return join_path(&self.prefix_of(&mount.file, &mount.receiver, depth + 1), &mount.prefix);
+        }
+
+        let is_exported = self.exports.iter()
+            .any(|(export_file, name)| export_file == file && (name == receiver || name == "default"));
+        if !is_exported {
+            return "".to_string();
+        }
+
+        let imported = self.mounts.iter().find(|mount| {
+            self.imports.iter().any(|(import_file, name, source)| {
+                import_file == &mount.file && name == &mount.router && is_module_of(source, file)
+            })
+        });
+        match imported {
+            Some(mount) => join_path(&self.prefix_of(&mount.file, &mount.receiver, depth + 1), &mount.prefix),
+            None => "".to_string(),
+        }
+    }
+}
+
+/// Chapi keeps the variable of `router.get(...)` as node name, or as origin node name when the
+/// node name is the resolved type.
+fn receiver(call: &CodeCall) -> &str {
+    if call.origin_node_name.is_empty() { &call.node_name } else { &call.origin_node_name }
+}
+
+/// Whether a relative import like `./routes/users` points to `src/routes/users.ts`, or to
+/// `src/routes/users/index.ts`.
+fn is_module_of(source: &str, file: &str) -> bool {
+    if !source.starts_with('.') {
+        return false;
+    }
+
+    let module: Vec<&str> = source.split('/')
+        .filter(|it| !it.is_empty() && *it != "." && *it != "..")
+        .collect();
+    let without_extension = file.rsplit_once('.').map_or(file, |(path, _)| path);
+    let mut segments: Vec<&str> = without_extension.split('/').collect();
+
+    if segments.ends_with(&module) {
+        return true;
+    }
+    if segments.last() == Some(&"index") {
+        segments.pop();
+        return segments.ends_with(&module);
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model::analyser::ApiAnalyser;
+    use crate::model::analyser::typescript_api_analyser::TypeScriptApiAnalyser;
+    use crate::model::CodeDataStruct;
+
+    fn analyse(name: &str) -> Vec<String> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).parent()
+            .unwrap()
+            .join("_fixtures/typescript/api")
+            .join(name);
+        let nodes: Vec<CodeDataStruct> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+
+        let mut analyser = TypeScriptApiAnalyser::new();
+        analyser.analysis(&nodes, "");
+        analyser.to_container_services()[0].resources.iter()
+            .map(|it| format!("{} {} {}", it.source_http_method, it.source_url, it.method_name).trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn should_analyse_express_routes_and_mounted_routers() {
+        assert_eq!(analyse("express.json"), vec![
+            "Get /health health",
+            "Get /api/users/:id getUser",
+            "Post /api/users createUser",
+            "Delete /api/users/:id",
+        ]);
+    }
+
+    #[test]
+    fn should_analyse_nest_controller() {
+        assert_eq!(analyse("nestjs.json"), vec![
+            "Get /cats findAll",
+            "Get /cats/:id findOne",
+            "Post /cats create",
+            "Delete /cats/:id remove",
+        ]);
+    }
+}
@@ -19,6 +19,12 @@ pub struct ContainerSupply {
     pub(crate) package_name: String,
     pub(crate) class_name: String,
     pub(crate) method_name: String,
+    /// Media types of the response, from the `@Produces` of JAX-RS
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) produces: Vec<String>,
+    /// Media types of the request body, from the `@Consumes` of JAX-RS
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) consumes: Vec<String>,
 }
 
 impl ContainerSupply {
@@ -45,25 +51,31 @@ pub struct ContainerDemand {
     pub(crate) call_data: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct CodeDatabaseRelation {
-    package_name: String,
-    class_name: String,
-    function_name: String,
+    pub(crate) package_name: String,
+    pub(crate) class_name: String,
+    pub(crate) function_name: String,
     #[serde(default)]
-    tables: Vec<String>,
+    pub(crate) tables: Vec<String>,
     #[serde(default)]
-    sqls: Vec<String>,
+    pub(crate) sqls: Vec<String>,
     #[serde(default)]
-    implementations: Vec<String>,
+    pub(crate) implementations: Vec<String>,
     #[serde(default)]
-    relations: Vec<NodeRelation>,
+    pub(crate) relations: Vec<NodeRelation>,
+}
+
+impl CodeDatabaseRelation {
+    pub fn canonical_name(&self) -> String {
+        format!("{}.{}.{}", self.package_name, self.class_name, self.function_name)
+    }
 }
 
 impl std::fmt::Display for CodeDatabaseRelation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "source: {}.{}.{}", self.package_name, self.class_name, self.function_name)?;
+        write!(f, "source: {}", self.canonical_name())?;
         if !self.sqls.is_empty() {
             write!(f, "\nsqls: {:?}", self.sqls)?;
         }
@@ -75,11 +87,11 @@ impl std::fmt::Display for CodeDatabaseRelation {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct NodeRelation {
-    source: String,
-    target: String,
+    pub(crate) source: String,
+    pub(crate) target: String,
 }
 
 impl std::fmt::Display for NodeRelation {
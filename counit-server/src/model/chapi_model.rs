@@ -9,7 +9,8 @@ pub struct CodeDataStruct {
     pub(crate) node_name: String,
     #[serde(default = "String::new")]
     pub(crate) module: String,
-    #[serde(default = "DataStructType::default")]
+    // Chapi names it `Type`, like `"Type": "CLASS"`
+    #[serde(default = "DataStructType::default", alias = "Type")]
     pub(crate) data_type: DataStructType,
     // You need to define DataStructType enum separately
     #[serde(default = "String::new")]
@@ -62,18 +63,33 @@ impl CodeDataStruct {
 
 #[derive(Serialize, Deserialize, Clone)]
 pub enum DataStructType {
+    #[serde(alias = "EMPTY")]
     Empty,
+    #[serde(alias = "DEFAULT")]
     Default,
+    #[serde(alias = "CLASS")]
     Class,
+    #[serde(alias = "VARIABLE")]
     Variable,
+    #[serde(alias = "INTERFACE")]
     Interface,
+    #[serde(alias = "STRUCT")]
     Struct,
+    #[serde(alias = "OBJECT")]
     Object,
+    #[serde(alias = "INNER_STRUCTURES")]
     InnerStructures,
+    #[serde(alias = "CREATOR_CLASS")]
     CreatorClass,
+    #[serde(alias = "ABSTRACT_CLASS")]
     AbstractClass,
+    #[serde(alias = "TRAIT")]
     Trait,
+    #[serde(alias = "ENUM")]
     Enum,
+    /// A type of a newer Chapi version
+    #[serde(other)]
+    Unknown,
 }
 
 impl DataStructType {
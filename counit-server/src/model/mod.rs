@@ -7,4 +7,5 @@ pub mod chapi_model;
 
 pub mod archguard_model;
 pub mod archguard_openapi;
+pub mod openapi_document;
 pub mod dto;
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde::Serialize;
+
+/// An OpenAPI 3 document, only the parts we can derive from code are modelled.
+#[derive(Serialize, Debug, Clone)]
+pub struct OpenApiDocument {
+    pub openapi: String,
+    pub info: Info,
+    /// path -> lowercase http method -> operation
+    pub paths: BTreeMap<String, BTreeMap<String, Operation>>,
+    pub components: Components,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Info {
+    pub title: String,
+    pub version: String,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Components {
+    pub schemas: BTreeMap<String, Schema>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Operation {
+    pub tags: Vec<String>,
+    pub operation_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<Parameter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<RequestBody>,
+    pub responses: BTreeMap<String, ApiResponse>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    /// path, query, header or cookie
+    #[serde(rename = "in")]
+    pub location: String,
+    pub required: bool,
+    pub schema: Schema,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RequestBody {
+    pub required: bool,
+    pub content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MediaType {
+    pub schema: Schema,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ApiResponse {
+    pub description: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub content: BTreeMap<String, MediaType>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Schema {
+    #[serde(rename = "$ref", skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub schema_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<Schema>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, Schema>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_properties: Option<Box<Schema>>,
+    #[serde(rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub enum_values: Vec<String>,
+}
+
+impl Schema {
+    pub fn of_type(schema_type: &str, format: Option<&str>) -> Schema {
+        Schema {
+            schema_type: Some(schema_type.to_string()),
+            format: format.map(|it| it.to_string()),
+            ..Schema::default()
+        }
+    }
+
+    pub fn reference(name: &str) -> Schema {
+        Schema {
+            reference: Some(format!("#/components/schemas/{}", name)),
+            ..Schema::default()
+        }
+    }
+
+    pub fn array(items: Schema) -> Schema {
+        Schema {
+            schema_type: Some("array".to_string()),
+            items: Some(Box::new(items)),
+            ..Schema::default()
+        }
+    }
+
+    /// A short name for the index text, like `integer`, `Blog` or `Blog[]`.
+    pub fn display(&self) -> String {
+        if let Some(reference) = &self.reference {
+            return reference.rsplit('/').next().unwrap_or_default().to_string();
+        }
+        if let Some(items) = &self.items {
+            return format!("{}[]", items.display());
+        }
+
+        self.schema_type.clone().unwrap_or("object".to_string())
+    }
+}
+
+impl Operation {
+    /// The text to index for semantic search, like
+    /// `GET /api/blogs/{id} getBlogById(id: integer) -> Blog: get a blog`.
+    pub fn display(&self, method: &str, path: &str) -> String {
+        let mut params: Vec<String> = self.parameters.iter()
+            .map(|it| format!("{}: {}", it.name, it.schema.display()))
+            .collect();
+        if let Some(body) = &self.request_body {
+            if let Some(media) = body.content.values().next() {
+                params.push(format!("body: {}", media.schema.display()));
+            }
+        }
+
+        let mut text = format!("{} {} {}({})", method.to_uppercase(), path, self.operation_id, params.join(", "));
+        let returns = self.responses.values()
+            .flat_map(|it| it.content.values())
+            .next();
+        if let Some(media) = returns {
+            text.push_str(&format!(" -> {}", media.schema.display()));
+        }
+        if let Some(summary) = &self.summary {
+            text.push_str(&format!(": {}", summary));
+        }
+
+        text
+    }
+}
+
+impl OpenApiDocument {
+    /// Every operation of the document with the text to index.
+    pub fn display_texts(&self) -> Vec<String> {
+        self.paths.iter()
+            .flat_map(|(path, operations)| {
+                operations.iter().map(move |(method, operation)| operation.display(method, path))
+            })
+            .collect()
+    }
+}
+
+/// A generated document, together with the service it describes.
+#[derive(Clone)]
+pub struct OpenApiEntry {
+    pub system_id: String,
+    pub repo_id: String,
+    pub document: OpenApiDocument,
+}
+
+/// In-memory store of the generated documents, so that they can be served after generation.
+/// Generating the document of a repo again replaces the previous one.
+#[derive(Default)]
+pub struct OpenApiStore {
+    entries: RwLock<Vec<OpenApiEntry>>,
+}
+
+impl OpenApiStore {
+    pub fn save(&self, system_id: &str, repo_id: &str, document: OpenApiDocument) {
+        let mut entries = self.entries.write().unwrap();
+        entries.retain(|it| !(it.system_id == system_id && it.repo_id == repo_id));
+        entries.push(OpenApiEntry {
+            system_id: system_id.to_string(),
+            repo_id: repo_id.to_string(),
+            document,
+        });
+    }
+
+    pub fn get(&self, system_id: &str, repo_id: &str) -> Option<OpenApiDocument> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .find(|it| it.system_id == system_id && it.repo_id == repo_id)
+            .map(|it| it.document.clone())
+    }
+
+    /// The `(system_id, repo_id)` of the documents, in the order they were saved.
+    pub fn keys(&self) -> Vec<(String, String)> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|it| (it.system_id.clone(), it.repo_id.clone()))
+            .collect()
+    }
+}
@@ -4,6 +4,7 @@ pub mod semantic;
 pub mod semantic_query;
 pub mod literal;
 pub mod payload;
+pub mod path_index;
 
 /// Generate a content hash from the embedding data, and pin it to
 /// the containing file's content id.
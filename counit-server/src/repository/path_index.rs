@@ -0,0 +1,229 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::RwLock;
+
+use serde::Serialize;
+
+/// A path matching a path search, the higher the score the better the match.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PathMatch {
+    pub repo: String,
+    pub path: String,
+    pub score: f32,
+}
+
+/// The relative paths of the files of each repo, which are indexed while the code of the repo
+/// is ingested, for the fuzzy path search of the agent's `path` tool.
+#[derive(Default)]
+pub struct PathIndex {
+    repos: RwLock<HashMap<String, BTreeSet<String>>>,
+}
+
+impl PathIndex {
+    /// Replace the paths of the repo, when it is ingested again, so that the deleted or renamed
+    /// files are no longer found.
+    pub fn replace<'a>(&self, repo: &str, paths: impl IntoIterator<Item = &'a str>) {
+        self.repos.write().unwrap().remove(repo);
+        self.add(repo, paths);
+    }
+
+    pub fn add<'a>(&self, repo: &str, paths: impl IntoIterator<Item = &'a str>) {
+        let mut repos = self.repos.write().unwrap();
+        insert(repos.entry(repo.to_string()).or_default(), paths);
+    }
+
+    /// Add the paths of a repo which is not indexed yet, like the paths loaded from Qdrant at
+    /// startup, which must not undo a [PathIndex::replace] made meanwhile. Returns false if the
+    /// repo is already indexed.
+    pub fn seed<'a>(&self, repo: &str, paths: impl IntoIterator<Item = &'a str>) -> bool {
+        let mut repos = self.repos.write().unwrap();
+        if repos.contains_key(repo) {
+            return false;
+        }
+
+        insert(repos.entry(repo.to_string()).or_default(), paths);
+        true
+    }
+
+    pub fn path_count(&self, repo: &str) -> usize {
+        self.repos.read().unwrap().get(repo).map_or(0, |it| it.len())
+    }
+
+    /// The paths matching every term of the query, in the given repos or in all of them if none
+    /// is given, the best matches first.
+    pub fn search(&self, query: &str, repos: &[String], limit: usize) -> Vec<PathMatch> {
+        let terms: Vec<String> = query.split_whitespace().map(|it| it.to_lowercase()).collect();
+        if terms.is_empty() {
+            return vec![];
+        }
+
+        let index = self.repos.read().unwrap();
+        let mut matches: Vec<PathMatch> = index.iter()
+            .filter(|(repo, _)| repos.is_empty() || repos.contains(repo))
+            .flat_map(|(repo, paths)| paths.iter().map(move |path| (repo, path)))
+            .filter_map(|(repo, path)| {
+                let lowercase = path.to_lowercase();
+                let score = terms.iter()
+                    .map(|term| score(term, &lowercase))
+                    .sum::<Option<f32>>()?;
+                Some(PathMatch { repo: repo.clone(), path: path.clone(), score })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score.total_cmp(&a.score)
+                .then(a.path.len().cmp(&b.path.len()))
+                .then(a.path.cmp(&b.path))
+        });
+        matches.truncate(limit);
+        matches
+    }
+}
+
+fn insert<'a>(entry: &mut BTreeSet<String>, paths: impl IntoIterator<Item = &'a str>) {
+    for path in paths {
+        let path = path.trim().trim_start_matches("./").replace('\\', "/");
+        if !path.is_empty() {
+            entry.insert(path);
+        }
+    }
+}
+
+/// How well a lowercase term matches a lowercase path, from the best to the worst:
+///
+/// - the path contains the term, better in the file name, like `payment` in `src/PaymentService.java`
+/// - the characters of the term are in the path in order, like `pmtsvc`, better if they are
+///   consecutive
+/// - a part of the file name is a few edits away from the term, like `servise`
+fn score(term: &str, path: &str) -> Option<f32> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    if path.contains(term) {
+        let in_file_name = if file_name.contains(term) { 20.0 } else { 0.0 };
+        return Some(100.0 + in_file_name + 10.0 * term.len() as f32 / path.len() as f32);
+    }
+
+    if let Some(consecutive) = subsequence_score(term, path) {
+        return Some(50.0 + 20.0 * consecutive);
+    }
+
+    let max_distance = (term.chars().count() / 4).max(1);
+    let distance = substring_distance(term, file_name);
+    if distance <= max_distance {
+        return Some(10.0 - distance as f32);
+    }
+
+    None
+}
+
+/// The share of consecutive characters, if every character of the term is in the text in order.
+fn subsequence_score(term: &str, text: &str) -> Option<f32> {
+    let mut text = text.chars();
+    let mut consecutive = 0;
+    let mut first = true;
+    for char in term.chars() {
+        let mut skipped = false;
+        loop {
+            match text.next() {
+                Some(it) if it == char => break,
+                Some(_) => skipped = true,
+                None => return None,
+            }
+        }
+        if !first && !skipped {
+            consecutive += 1;
+        }
+        first = false;
+    }
+
+    let pairs = term.chars().count().saturating_sub(1).max(1);
+    Some(consecutive as f32 / pairs as f32)
+}
+
+/// The edit distance between the term and the closest substring of the text.
+fn substring_distance(term: &str, text: &str) -> usize {
+    let text: Vec<char> = text.chars().collect();
+    // a match may start anywhere in the text
+    let mut previous: Vec<usize> = vec![0; text.len() + 1];
+    for (i, term_char) in term.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, text_char) in text.iter().enumerate() {
+            let substitution = previous[j] + usize::from(term_char != *text_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    // and end anywhere
+    previous.into_iter().min().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::path_index::{PathIndex, substring_distance};
+
+    fn index() -> PathIndex {
+        let index = PathIndex::default();
+        index.add("payment", [
+            "src/main/java/cc/unitmesh/payment/PaymentService.java",
+            "src/main/java/cc/unitmesh/payment/PaymentController.java",
+            "src/main/java/cc/unitmesh/refund/RefundService.java",
+            "./README.md",
+        ]);
+        index.add("blog", ["src/main/kotlin/cc/unitmesh/blog/BlogService.kt"]);
+        index
+    }
+
+    fn paths(query: &str, repos: &[String]) -> Vec<String> {
+        index().search(query, repos, 10).into_iter().map(|it| it.path).collect()
+    }
+
+    #[test]
+    fn should_rank_substring_then_subsequence_then_typo() {
+        assert_eq!(paths("paymentservice", &[]), vec!["src/main/java/cc/unitmesh/payment/PaymentService.java"]);
+        assert_eq!(paths("refsvc", &[]), vec!["src/main/java/cc/unitmesh/refund/RefundService.java"]);
+        assert_eq!(paths("readme", &[]), vec!["README.md"]);
+        assert_eq!(paths("servise", &[]).len(), 3);
+
+        let service = paths("service", &[]);
+        assert_eq!(service.len(), 3);
+        assert_eq!(service[0], "src/main/kotlin/cc/unitmesh/blog/BlogService.kt");
+    }
+
+    #[test]
+    fn should_match_every_term_in_the_given_repos() {
+        assert_eq!(paths("payment controller", &[]), vec!["src/main/java/cc/unitmesh/payment/PaymentController.java"]);
+        assert_eq!(paths("service", &["blog".to_string()]), vec!["src/main/kotlin/cc/unitmesh/blog/BlogService.kt"]);
+        assert!(paths("  ", &[]).is_empty());
+        assert!(paths("xyzzy", &[]).is_empty());
+        assert_eq!(index().path_count("payment"), 4);
+    }
+
+    #[test]
+    fn should_replace_the_paths_of_a_reingested_repo() {
+        let index = index();
+        index.replace("payment", ["src/main/java/cc/unitmesh/payment/PaymentApi.java"]);
+
+        assert_eq!(index.path_count("payment"), 1);
+        let paths: Vec<String> = index.search("payment", &["payment".to_string()], 10).into_iter().map(|it| it.path).collect();
+        assert_eq!(paths, vec!["src/main/java/cc/unitmesh/payment/PaymentApi.java"]);
+        assert_eq!(index.path_count("blog"), 1);
+    }
+
+    #[test]
+    fn should_only_seed_the_repos_which_are_not_indexed() {
+        let index = index();
+        index.replace("payment", ["src/main/java/cc/unitmesh/payment/PaymentApi.java"]);
+
+        assert!(!index.seed("payment", ["src/main/java/cc/unitmesh/payment/OldPaymentApi.java"]));
+        assert_eq!(index.path_count("payment"), 1);
+        assert!(index.seed("order", ["./src/OrderApi.java"]));
+        assert_eq!(index.search("order", &[], 10)[0].path, "src/OrderApi.java");
+    }
+
+    #[test]
+    fn should_compute_edit_distance_to_closest_substring() {
+        assert_eq!(substring_distance("service", "paymentservice.java"), 0);
+        assert_eq!(substring_distance("servise", "paymentservice.java"), 1);
+        assert_eq!(substring_distance("kitten", "sitting"), 2);
+        assert_eq!(substring_distance("abc", ""), 3);
+    }
+}
@@ -115,6 +115,9 @@ impl PartialEq for CodePayload {
     }
 }
 
+/// The payload fields of [CodePayload::path_from_scroll].
+pub const PATH_FIELDS: [&str; 2] = ["repo_name", "relative_path"];
+
 macro_rules! val_str (($hash:ident, $val:expr) => { serde_json::from_value($hash.remove($val).unwrap()).unwrap() });
 macro_rules! val_parse_str (($hash:ident, $val:expr) => {
     serde_json::from_value::<Cow<'_, str>>($hash.remove($val).unwrap())
@@ -147,6 +150,18 @@ impl CodePayload {
         parse_payload(id, vectors, payload, 0.0)
     }
 
+    /// The repo and the relative path of a point scrolled with only these two fields, see
+    /// [PATH_FIELDS].
+    pub fn path_from_scroll(orig: RetrievedPoint) -> Option<(String, String)> {
+        let mut payload = orig.payload;
+        let mut field = |name: &str| match kind_to_value(payload.remove(name)?.kind) {
+            serde_json::Value::String(value) => Some(value),
+            _ => None,
+        };
+
+        Some((field("repo_name")?, field("relative_path")?))
+    }
+
     pub(crate) fn into_qdrant(self) -> HashMap<String, Value> {
         HashMap::from([
             ("lang".into(), self.lang.to_ascii_lowercase().into()),
@@ -14,7 +14,7 @@ use qdrant_client::{
     qdrant::{
         CollectionOperationResponse, CreateCollection,
         Distance, FieldCondition, FieldType,
-        Filter, Match, PointId, r#match::MatchValue, ScoredPoint, SearchPoints, VectorParams,
+        Filter, Match, PayloadIncludeSelector, PointId, r#match::MatchValue, ScoredPoint, ScrollPoints, SearchPoints, VectorParams,
         vectors_config, VectorsConfig, with_payload_selector, with_vectors_selector,
         WithPayloadSelector, WithVectorsSelector,
     },
@@ -25,7 +25,7 @@ use tracing::{debug, info, trace};
 
 use crate::configuration::Configuration;
 use crate::repository::cache_key;
-use crate::repository::payload::{CodePayload, PayloadType};
+use crate::repository::payload::{CodePayload, PATH_FIELDS, PayloadType};
 use crate::repository::semantic_query::SemanticQuery;
 
 #[derive(Clone)]
@@ -58,6 +58,8 @@ pub enum SemanticError {
 
 pub(crate) const COLLECTION_NAME: &str = "documents";
 pub(crate) const EMBEDDING_DIM: usize = 384;
+/// The points of a page when scrolling the collection
+const SCROLL_LIMIT: u32 = 1000;
 
 pub type Embedding = Vec<f32>;
 
@@ -294,6 +296,23 @@ impl Semantic {
         threshold: f32,
         retrieve_more: bool,
     ) -> anyhow::Result<Vec<CodePayload>> {
+        let results = self
+            .batch_search_with_sources(parsed_queries, limit, offset, threshold, retrieve_more)
+            .await?;
+
+        Ok(results.into_iter().map(|(payload, _)| payload).collect())
+    }
+
+    /// Like [Semantic::batch_search], with the indexes of the queries whose vectors found each
+    /// snippet.
+    pub async fn batch_search_with_sources<'a>(
+        &self,
+        parsed_queries: &[&SemanticQuery<'a>],
+        limit: u64,
+        offset: u64,
+        threshold: f32,
+        retrieve_more: bool,
+    ) -> anyhow::Result<Vec<(CodePayload, Vec<usize>)>> {
         if parsed_queries.iter().any(|q| q.target().is_none()) {
             anyhow::bail!("no search target for query");
         };
@@ -306,7 +325,7 @@ impl Semantic {
         tracing::trace!(?parsed_queries, "performing qdrant batch search");
 
         let result = self
-            .batch_search_with(
+            .search_each_vector(
                 parsed_queries,
                 vectors.clone(),
                 if retrieve_more { limit * 2 } else { limit }, // Retrieve double `limit` and deduplicate
@@ -319,13 +338,23 @@ impl Semantic {
 
         let results = result?
             .into_iter()
-            .map(CodePayload::from_qdrant)
-            .collect::<Vec<_>>();
+            .map(|points| points.into_iter().map(CodePayload::from_qdrant).collect())
+            .collect();
+        let (results, sources) = merge_sources(results);
 
         // deduplicate with mmr with respect to the mean of query vectors
         // TODO: implement a more robust multi-vector deduplication strategy
         let target_vector = mean_pool(vectors);
-        Ok(deduplicate_snippets(results, target_vector, limit))
+        Ok(deduplicate_snippets(results, target_vector, limit)
+            .into_iter()
+            .map(|payload| {
+                let source = payload.id.as_ref()
+                    .and_then(|id| sources.get(id))
+                    .cloned()
+                    .unwrap_or_default();
+                (payload, source)
+            })
+            .collect())
     }
 
     pub async fn batch_search_with<'a>(
@@ -336,6 +365,22 @@ impl Semantic {
         offset: u64,
         threshold: f32,
     ) -> anyhow::Result<Vec<ScoredPoint>> {
+        let responses = self
+            .search_each_vector(parsed_queries, vectors, limit, offset, threshold)
+            .await?;
+
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    /// The points found by each vector, in the order of the vectors.
+    async fn search_each_vector<'a>(
+        &self,
+        parsed_queries: &[&SemanticQuery<'a>],
+        vectors: Vec<Embedding>,
+        limit: u64,
+        offset: u64,
+        threshold: f32,
+    ) -> anyhow::Result<Vec<Vec<ScoredPoint>>> {
         // FIXME: This method uses `search_points` internally, and not `search_batch_points`. It's
         // not clear why, but it seems that the `batch` variant of the `qdrant` calls leads to
         // HTTP2 errors on some deployment configurations. A typical example error:
@@ -382,10 +427,47 @@ impl Semantic {
             .try_collect::<Vec<_>>()
             .await?;
 
-        Ok(responses.into_iter().flat_map(|r| r.result).collect())
+        Ok(responses.into_iter().map(|r| r.result).collect())
     }
 
 
+    /// The repo and the relative path of the code points, page by page, to rebuild the path
+    /// index of the ingested repos.
+    pub async fn code_paths(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let mut paths = vec![];
+        let mut offset = None;
+        loop {
+            let response = self
+                .qdrant
+                .scroll(&ScrollPoints {
+                    collection_name: COLLECTION_NAME.to_string(),
+                    filter: Some(Filter {
+                        must: vec![make_kv_keyword_filter("payload_type", &PayloadType::Code.to_string()).into()],
+                        ..Default::default()
+                    }),
+                    offset,
+                    limit: Some(SCROLL_LIMIT),
+                    // only the two fields, not the code of the whole repo
+                    with_payload: Some(WithPayloadSelector {
+                        selector_options: Some(with_payload_selector::SelectorOptions::Include(PayloadIncludeSelector {
+                            fields: PATH_FIELDS.iter().map(|it| it.to_string()).collect(),
+                        })),
+                    }),
+                    with_vectors: Some(WithVectorsSelector {
+                        selector_options: Some(with_vectors_selector::SelectorOptions::Enable(false)),
+                    }),
+                    ..Default::default()
+                })
+                .await?;
+
+            paths.extend(response.result.into_iter().filter_map(CodePayload::path_from_scroll));
+            match response.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => return Ok(paths),
+            }
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     #[tracing::instrument(skip(self, repo_name, buffer))]
     pub async fn insert_points_for_buffer(
@@ -438,6 +520,37 @@ impl Semantic {
     }
 }
 
+/// Merge the snippets found by each vector, a snippet found by several vectors is kept once, with
+/// its best score, and the indexes of the vectors which found it.
+fn merge_sources(results: Vec<Vec<CodePayload>>) -> (Vec<CodePayload>, HashMap<String, Vec<usize>>) {
+    let mut merged: Vec<CodePayload> = vec![];
+    let mut sources: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, payloads) in results.into_iter().enumerate() {
+        for payload in payloads {
+            let Some(id) = payload.id.clone() else {
+                merged.push(payload);
+                continue;
+            };
+
+            let source = sources.entry(id.clone()).or_default();
+            if !source.is_empty() {
+                if let Some(existing) = merged.iter_mut().find(|it| it.id.as_ref() == Some(&id)) {
+                    if payload.score > existing.score {
+                        existing.score = payload.score;
+                    }
+                }
+            } else {
+                merged.push(payload);
+            }
+            if !source.contains(&index) {
+                source.push(index);
+            }
+        }
+    }
+
+    (merged, sources)
+}
+
 pub fn deduplicate_snippets(
     mut all_snippets: Vec<CodePayload>,
     query_embedding: Embedding,
@@ -735,7 +848,8 @@ mod tests {
     use std::path::Path;
     use std::sync::Arc;
     use crate::configuration::Configuration;
-    use crate::repository::semantic::Semantic;
+    use crate::repository::payload::CodePayload;
+    use crate::repository::semantic::{merge_sources, Semantic};
 
     #[tokio::test]
     async fn test_mmr() {
@@ -747,4 +861,24 @@ mod tests {
         let result = semantic.embed("blog");
         println!("{:?}", result.unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn should_merge_snippets_found_by_several_vectors() {
+        let payload = |id: &str, score: f32| CodePayload {
+            id: Some(id.to_string()),
+            score: Some(score),
+            ..Default::default()
+        };
+
+        let (merged, sources) = merge_sources(vec![
+            vec![payload("a", 0.5), payload("b", 0.4)],
+            vec![payload("c", 0.7), payload("a", 0.9)],
+        ]);
+
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].score, Some(0.9));
+        assert_eq!(sources["a"], vec![0, 1]);
+        assert_eq!(sources["b"], vec![0]);
+        assert_eq!(sources["c"], vec![1]);
+    }
+}
@@ -4,6 +4,15 @@ use std::collections::HashSet;
 use crate::repository::literal::Literal;
 use crate::repository::payload::PayloadType;
 
+/// The text of a multi-vector search whose vector found a snippet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchVector {
+    Query,
+    NatureLangQuery,
+    HypotheticalDocument,
+}
+
 #[derive(Default, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct SemanticQuery<'a> {
     pub repos: HashSet<Literal<'a>>,
@@ -1,19 +1,47 @@
-use axum::{Extension, extract::Query, Json, Router};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use axum::{Extension, extract::{Path, Query}, Json, response::IntoResponse, Router};
 use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
 
+use crate::agent::agent::Agent;
+use crate::agent::conversation::{Conversation, ConversationSummary};
+use crate::agent::exchange::Exchange;
 use crate::agent::prompts::tool_prompt;
+use crate::agent::templates::{PromptLanguage, TemplateInfo};
+use crate::agent::tools::{function_schemas, ToolFormat, tools_list};
 use crate::application::Application;
-use crate::dsl::query_description::QAExample;
+use crate::dsl::example_library::{Embedder, PROMPT_EXAMPLE_COUNT};
+use crate::dsl::query_description::{ExplainQuery, QAExample};
 use crate::model::dto::query::SimpleQuery;
+use crate::repository::literal::Literal;
+use crate::repository::payload::PayloadType;
+use crate::repository::semantic_query::{SearchVector, SemanticQuery};
+use crate::server::{Error, ErrorKind, json};
+use crate::server::semantic_api::{search_hits, SearchHit};
 
 pub(crate) fn router() -> Router {
     use axum::routing::*;
 
     Router::new()
         .route("/prompt/explain", get(explain_query))
+        .route("/explain", get(explain))
+        .route("/explain/search", post(explain_search))
 
         .route("/prompt/functions/matching", post(tool_prompter))
+        .route("/tools", get(tool_definitions))
+        .route("/prompt/templates", get(list_templates))
+        .route("/prompt/templates/:name/render", post(render_template))
+
+        .route("/answer", get(answer))
+        .route("/answer/stream", get(answer_stream))
+
+        .route("/threads", get(list_threads))
+        .route("/threads/:threadId", get(get_thread).delete(delete_thread))
 }
 
 #[derive(Serialize)]
@@ -21,16 +49,134 @@ pub struct PromptResult {
     pub prompt: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExplainArgs {
+    pub q: String,
+    /// the domain of the examples of the prompt, all the examples are used when it has none
+    pub domain: Option<String>,
+}
+
 pub(crate) async fn explain_query(
-    Query(args): Query<SimpleQuery>,
+    Query(args): Query<ExplainArgs>,
     Extension(app): Extension<Application>,
-) -> (StatusCode, Json<PromptResult>) {
+) -> impl IntoResponse {
     let query = app.transpiler.transpile(&args.q);
+    let examples = explain_examples(&app, &query, args.domain.as_deref())?;
     let output = PromptResult {
-        prompt: QAExample::prompt(&query),
+        prompt: QAExample::prompt(&app.templates, &examples, &query),
     };
 
-    (StatusCode::OK, Json(output))
+    Ok::<_, Error>((StatusCode::OK, Json(output)))
+}
+
+/// The examples of the library most similar to the query, for the explain prompt.
+fn explain_examples(app: &Application, query: &str, domain: Option<&str>) -> anyhow::Result<Vec<QAExample>> {
+    let embed = app.semantic.as_ref().map(|semantic| move |text: &str| semantic.embed(text));
+    app.examples.select(query, domain, PROMPT_EXAMPLE_COUNT, embed.as_ref().map(|it| it as Embedder))
+}
+
+#[derive(Serialize)]
+pub struct ExplainResult {
+    pub prompt: String,
+    /// the [crate::dsl::query_description::ExplainQuery] JSON written by the model
+    pub answer: String,
+}
+
+impl crate::server::ApiResponse for ExplainResult {}
+
+/// Let the model translate the query, after the glossary, with the explain prompt.
+pub(crate) async fn explain(
+    Query(args): Query<ExplainArgs>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let Some(llm) = app.model.client() else {
+        return Err(Error::new(ErrorKind::Configuration, "explaining a query needs `llm_base_url`"));
+    };
+
+    let query = app.transpiler.transpile(&args.q);
+    let examples = explain_examples(&app, &query, args.domain.as_deref())?;
+    let answer = QAExample::explain(llm.as_ref(), &app.templates, &examples, &query).await?;
+
+    Ok(json(ExplainResult {
+        prompt: QAExample::prompt(&app.templates, &examples, &query),
+        answer,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExplainSearchArgs {
+    /// the question, explained by the model when `explain` is not given
+    pub q: Option<String>,
+    /// the [ExplainQuery] written by the model for the question
+    pub explain: Option<ExplainQuery>,
+    /// the domain of the examples, when the question is explained
+    pub domain: Option<String>,
+    pub r#type: Option<PayloadType>,
+}
+
+#[derive(Serialize)]
+pub struct ExplainSearchResult {
+    pub explain: ExplainQuery,
+    /// whether the snippets were found in the repo of the domain, or in every repo as none of
+    /// them were
+    pub domain_matched: bool,
+    pub data: Vec<SearchHit>,
+}
+
+impl crate::server::ApiResponse for ExplainSearchResult {}
+
+/// Search with an [ExplainQuery]: the vectors of its query, natural language query and
+/// hypothetical document, in the repo of its domain.
+pub(crate) async fn explain_search(
+    Extension(app): Extension<Application>,
+    Json(args): Json<ExplainSearchArgs>,
+) -> impl IntoResponse {
+    let Some(semantic) = app.semantic.as_ref() else {
+        return Err(Error::new(ErrorKind::Configuration, "searching needs `qdrant_url`"));
+    };
+
+    let explain = match (args.explain, args.q) {
+        (Some(explain), _) => explain,
+        (None, Some(q)) => {
+            let Some(llm) = app.model.client() else {
+                return Err(Error::new(ErrorKind::Configuration, "explaining a query needs `llm_base_url`"));
+            };
+            let query = app.transpiler.transpile(&q);
+            let examples = explain_examples(&app, &query, args.domain.as_deref())?;
+            let answer = QAExample::explain(llm.as_ref(), &app.templates, &examples, &query).await?;
+            ExplainQuery::from_answer(&answer).map_err(|err| {
+                Error::new(ErrorKind::UpstreamService, format!("the model's explanation can't be parsed: {:#}", err))
+                    .with_status(StatusCode::BAD_GATEWAY)
+            })?
+        }
+        (None, None) => return Err(Error::user("either `q` or `explain` is needed")),
+    };
+
+    let mut queries = explain.semantic_queries(args.r#type);
+    if queries.is_empty() {
+        return Err(Error::user("the explained query has no text to search"));
+    }
+
+    let vectors: Vec<SearchVector> = queries.iter().map(|(vector, _)| *vector).collect();
+    let mut domain_matched = !explain.domain.trim().is_empty();
+    let mut results = semantic
+        .batch_search_with_sources(&queries.iter().map(|(_, query)| query).collect::<Vec<_>>(), 10, 0, 0.0, true)
+        .await?;
+
+    // the domain may not be the name of a repo
+    if results.is_empty() && domain_matched {
+        domain_matched = false;
+        queries.iter_mut().for_each(|(_, query)| query.repos.clear());
+        results = semantic
+            .batch_search_with_sources(&queries.iter().map(|(_, query)| query).collect::<Vec<_>>(), 10, 0, 0.0, true)
+            .await?;
+    }
+
+    Ok(json(ExplainSearchResult {
+        explain,
+        domain_matched,
+        data: search_hits(results, &vectors),
+    }))
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,13 +186,221 @@ pub struct PathListArgs {
 
 pub(crate) async fn tool_prompter(
     Query(args): Query<SimpleQuery>,
+    Extension(app): Extension<Application>,
 ) -> (StatusCode, Json<PromptResult>) {
-    let paths = vec![args.q];
     let output = PromptResult {
-        prompt: tool_prompt(&paths),
+        prompt: tool_prompt(&app.templates, &[(0, args.q.as_str())]),
     };
 
     (StatusCode::OK, Json(output))
 }
 
 impl crate::server::ApiResponse for PromptResult {}
+
+/// The prompt templates, and whether they are overridden.
+pub(crate) async fn list_templates(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    json(app.templates.list())
+}
+
+impl crate::server::ApiResponse for Vec<TemplateInfo> {}
+
+#[derive(Debug, Deserialize)]
+pub struct RenderArgs {
+    /// the configured language if not given
+    pub lang: Option<PromptLanguage>,
+}
+
+/// Render a template with the variables of the body, to debug the prompts.
+pub(crate) async fn render_template(
+    Path(name): Path<String>,
+    Query(args): Query<RenderArgs>,
+    Extension(app): Extension<Application>,
+    Json(variables): Json<HashMap<String, String>>,
+) -> impl IntoResponse {
+    let language = args.lang.unwrap_or(app.templates.language());
+    let variables: Vec<(&str, &str)> = variables.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+
+    match app.templates.render_in(&name, language, &variables) {
+        Some(prompt) => Ok(json(PromptResult { prompt })),
+        None => Err(Error::new(ErrorKind::NotFound, format!("template {} not found", name))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToolArgs {
+    pub format: Option<ToolFormat>,
+}
+
+/// The definitions of the agent's tools for native function calling, in the OpenAI format by
+/// default, or in the text format of the tool prompt with `format=text`.
+pub(crate) async fn tool_definitions(
+    Query(args): Query<ToolArgs>,
+) -> impl IntoResponse {
+    match args.format.unwrap_or(ToolFormat::OpenAi) {
+        ToolFormat::Text => {
+            let tools = tools_list().iter().map(|it| it.to_string()).collect::<Vec<_>>().join("\n");
+            Json(serde_json::json!({ "prompt": tools }))
+        }
+        format => Json(function_schemas(format)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnswerArgs {
+    pub q: String,
+    /// only search the code of this repo
+    pub repo_ref: Option<String>,
+    /// follow up the questions of this thread
+    pub thread_id: Option<uuid::Uuid>,
+}
+
+fn check_agent_configuration(app: &Application) -> Result<(), Error> {
+    if app.model.client().is_none() || app.semantic.is_none() {
+        return Err(Error::new(ErrorKind::Configuration, "the agent needs `llm_base_url` and `qdrant_url`"));
+    }
+    Ok(())
+}
+
+/// The thread of the query and its exchanges, the previous ones of the thread followed by a new
+/// one for the query. A follow-up searches the repo of the previous question unless `repo_ref` is
+/// given.
+fn thread_exchanges(app: &Application, args: AnswerArgs) -> Result<(uuid::Uuid, Vec<Exchange>), Error> {
+    let (thread_id, mut exchanges) = match args.thread_id {
+        Some(thread_id) => {
+            let conversation = app.conversations.get(thread_id)
+                .ok_or(Error::new(ErrorKind::NotFound, format!("thread {} not found", thread_id)))?;
+            (thread_id, conversation.exchanges)
+        }
+        None => (uuid::Uuid::new_v4(), vec![]),
+    };
+
+    let mut query = SemanticQuery {
+        target: Some(Literal::Plain(Cow::Owned(args.q))),
+        ..Default::default()
+    };
+    match args.repo_ref {
+        Some(repo_ref) => query.repos = [Literal::Plain(Cow::Owned(repo_ref))].into(),
+        None => {
+            if let Some(previous) = exchanges.last() {
+                query.repos = previous.query.repos.clone();
+            }
+        }
+    }
+
+    exchanges.push(Exchange::new(uuid::Uuid::new_v4(), query));
+    Ok((thread_id, exchanges))
+}
+
+/// Let the agent search the codebase with its tools until it can answer the query.
+pub(crate) async fn answer(
+    Query(args): Query<AnswerArgs>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    check_agent_configuration(&app)?;
+    let (thread_id, exchanges) = thread_exchanges(&app, args)?;
+
+    // the answer is returned at once, so nobody listens to the updates
+    let (exchange_tx, _) = tokio::sync::mpsc::unbounded_channel();
+
+    let mut agent = Agent::new(app, exchanges, exchange_tx, thread_id);
+    agent.run().await?;
+
+    Ok::<_, Error>(json(AnswerResponse {
+        thread_id,
+        query_id: agent.query_id,
+        exchange: agent.exchanges.pop().unwrap_or_default(),
+    }))
+}
+
+/// Like [answer], but streams the agent's progress as server-sent events:
+///
+/// - `start`: the `thread_id` and `query_id`
+/// - `exchange`: the compressed exchange, after each search step and answer update
+/// - `done` once answered, or `error` with the message of the failure
+///
+/// The agent is cancelled when the client disconnects.
+pub(crate) async fn answer_stream(
+    Query(args): Query<AnswerArgs>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    check_agent_configuration(&app)?;
+    let (thread_id, exchanges) = thread_exchanges(&app, args)?;
+
+    let (exchange_tx, exchange_rx) = tokio::sync::mpsc::unbounded_channel();
+    let agent = Agent::new(app, exchanges, exchange_tx, thread_id);
+
+    let start = Event::default()
+        .event("start")
+        .json_data(serde_json::json!({ "thread_id": thread_id, "query_id": agent.query_id }))
+        .map_err(anyhow::Error::from)?;
+
+    // an update is an error event when the agent failed, which ends the stream without `done`
+    let updates = agent.run_stream(exchange_rx).map(|update| match update {
+        Ok(exchange) => Event::default()
+            .event("exchange")
+            .json_data(exchange.compressed())
+            .map_err(|err| Event::default().event("error").data(err.to_string())),
+        Err(err) => Err(Event::default().event("error").data(err.to_string())),
+    });
+    let done = futures::stream::once(async { Ok(Event::default().event("done").data("[DONE]")) });
+
+    let events = futures::stream::once(async { Ok(start) })
+        .chain(updates)
+        .chain(done)
+        .scan(false, |failed, event| {
+            let event = match event {
+                _ if *failed => None,
+                Ok(event) => Some(event),
+                Err(event) => {
+                    *failed = true;
+                    Some(event)
+                }
+            };
+            futures::future::ready(event)
+        })
+        .map(Ok::<_, Infallible>);
+
+    Ok::<_, Error>(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+impl crate::server::ApiResponse for AnswerResponse {}
+
+#[derive(Serialize)]
+pub struct AnswerResponse {
+    pub thread_id: uuid::Uuid,
+    pub query_id: uuid::Uuid,
+    pub exchange: Exchange,
+}
+
+impl crate::server::ApiResponse for Vec<ConversationSummary> {}
+
+impl crate::server::ApiResponse for Conversation {}
+
+/// The conversation threads, the most recently updated first.
+pub(crate) async fn list_threads(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    json(app.conversations.list())
+}
+
+pub(crate) async fn get_thread(
+    Path(thread_id): Path<uuid::Uuid>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    match app.conversations.get(thread_id) {
+        Some(conversation) => Ok(json(conversation)),
+        None => Err(Error::new(ErrorKind::NotFound, format!("thread {} not found", thread_id))),
+    }
+}
+
+pub(crate) async fn delete_thread(
+    Path(thread_id): Path<uuid::Uuid>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    if !app.conversations.delete(thread_id)? {
+        return Err(Error::new(ErrorKind::NotFound, format!("thread {} not found", thread_id)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,115 @@
+use axum::{
+    Extension,
+    extract::{Path, Query},
+    Json, response::IntoResponse, Router,
+};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::application::Application;
+use crate::model::{CodeDataStruct, ContainerService};
+use crate::model::analyser::analyse_container_services;
+use crate::model::analyser::spring_openapi::generate_spring_openapi;
+use crate::model::openapi_document::OpenApiDocument;
+use crate::repository::payload::PayloadType;
+use crate::server::{Error, ErrorKind, json};
+use crate::server::archguard_api::{ArchGuardParams, index_container_services};
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/:systemId/container-services", post(analyse_services))
+        .route("/:systemId/openapi", post(generate_openapi))
+        .route("/:systemId/openapi/:repoId", get(get_openapi))
+}
+
+/// Derive the http apis and api calls from the Chapi code data structs, the same as what ArchGuard
+/// reports to `/scanner/:systemId/reporting/container-services`.
+pub(crate) async fn analyse_services(
+    Extension(app): Extension<Application>,
+    Path(system_id): Path<u32>,
+    Query(params): Query<ArchGuardParams>,
+    Json(payload): Json<Vec<CodeDataStruct>>,
+) -> impl IntoResponse {
+    let Some(mut services) = analyse_container_services(&params.language, &payload, &params.path) else {
+        return Err(Error::user(format!("language `{}` is not supported", params.language)));
+    };
+
+    services.iter_mut()
+        .filter(|service| service.name.is_empty())
+        .for_each(|service| service.name = params.repo_id.clone());
+
+    let indexed = index_container_services(&app, system_id, &params, &services).await;
+
+    Ok(json(ContainerServicesResponse { data: services, indexed }))
+}
+
+impl crate::server::ApiResponse for ContainerServicesResponse {}
+
+#[derive(Serialize)]
+pub struct ContainerServicesResponse {
+    pub data: Vec<ContainerService>,
+    /// false if the services could not be indexed for semantic search
+    pub indexed: bool,
+}
+
+/// Generate the OpenAPI document of a Spring service, which is indexed for semantic search and
+/// kept to be served by [get_openapi].
+pub(crate) async fn generate_openapi(
+    Extension(app): Extension<Application>,
+    Path(system_id): Path<u32>,
+    Query(params): Query<ArchGuardParams>,
+    Json(payload): Json<Vec<CodeDataStruct>>,
+) -> impl IntoResponse {
+    if !matches!(params.language.to_lowercase().as_str(), "java" | "kotlin") {
+        return Err(Error::user(format!("language `{}` is not supported", params.language)));
+    }
+
+    let document = generate_spring_openapi(&params.repo_id, &payload);
+    app.openapi.save(&system_id.to_string(), &params.repo_id, document.clone());
+
+    let indexed = match app.semantic {
+        Some(ref semantic) => {
+            let mut indexed = true;
+            for display_text in document.display_texts() {
+                if let Err(err) = semantic.insert_points_for_buffer(
+                    params.repo_id.as_str(),
+                    params.repo_id.as_str(),
+                    params.path.as_str(),
+                    display_text.as_str(),
+                    params.language.as_str(),
+                    PayloadType::OpenApi,
+                    display_text.as_str(),
+                ).await {
+                    warn!(repo = %params.repo_id, "Failed to index the operation `{}`: {:#}", display_text, err);
+                    indexed = false;
+                }
+            }
+            indexed
+        }
+        None => false,
+    };
+
+    Ok(json(OpenApiResponse { data: document, indexed }))
+}
+
+/// The generated document as is, so that it can be used by OpenAPI tools.
+pub(crate) async fn get_openapi(
+    Extension(app): Extension<Application>,
+    Path((system_id, repo_id)): Path<(u32, String)>,
+) -> impl IntoResponse {
+    match app.openapi.get(&system_id.to_string(), &repo_id) {
+        Some(document) => Ok(Json(document)),
+        None => Err(Error::new(ErrorKind::NotFound, format!("no OpenAPI document for `{}`", repo_id))),
+    }
+}
+
+impl crate::server::ApiResponse for OpenApiResponse {}
+
+#[derive(Serialize)]
+pub struct OpenApiResponse {
+    pub data: OpenApiDocument,
+    /// false if the document could not be indexed for semantic search
+    pub indexed: bool,
+}
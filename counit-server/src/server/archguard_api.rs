@@ -5,6 +5,7 @@ use axum::{
 };
 use serde::Deserialize;
 use tokio::runtime::Handle;
+use tracing::warn;
 
 use crate::application::Application;
 use crate::model::{
@@ -26,9 +27,9 @@ pub fn router() -> Router {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ArchGuardParams {
-    language: String,
-    path: String,
-    repo_id: String,
+    pub(crate) language: String,
+    pub(crate) path: String,
+    pub(crate) repo_id: String,
     // repo_ref: String,
 }
 
@@ -73,13 +74,15 @@ pub async fn save_openapi(
 
 pub async fn save_datamap(
     Extension(app): Extension<Application>,
-    Path(_system_id): Path<u32>,
+    Path(system_id): Path<u32>,
     Query(params): Query<ArchGuardParams>,
     Json(payload): Json<Vec<CodeDatabaseRelation>>,
 ) -> (StatusCode, Json<()>) {
     let repo_ref = params.repo_id.clone();
     println!("save_datamap {:?}", repo_ref);
 
+    app.graph.save_datamaps(&system_id.to_string(), &params.repo_id, &payload);
+
     match app.semantic {
         Some(ref semantic) => {
             payload.iter().for_each(|relation| {
@@ -118,6 +121,8 @@ pub async fn save_class_items(
     let repo_ref = params.repo_id.clone();
     println!("save_class_items {:?}", repo_ref);
 
+    app.paths.replace(&params.repo_id, payload.iter().map(|class| class.file_path.as_str()));
+
     match app.semantic {
         Some(ref semantic) => {
             payload.iter().for_each(|class| {
@@ -126,12 +131,14 @@ pub async fn save_class_items(
                         Handle::current().block_on(async {
                             let display_text = &method.display(class);
                             let origin_content = &method.content;
+                            // the file of the class, so that the code search and the path index agree
+                            let relative_path = if class.file_path.is_empty() { &params.path } else { &class.file_path };
 
                             println!("class_items display_text {:?}", display_text);
                             let _ = semantic.insert_points_for_buffer(
                                 params.repo_id.as_str(),
                                 repo_ref.as_str(),
-                                params.path.as_str(),
+                                relative_path.as_str(),
                                 display_text.as_str(),
                                 params.language.as_str(),
                                 PayloadType::Code,
@@ -153,39 +160,53 @@ pub async fn save_class_items(
 
 pub async fn save_container(
     Extension(app): Extension<Application>,
-    Path(_system_id): Path<u32>,
+    Path(system_id): Path<u32>,
     Query(params): Query<ArchGuardParams>,
     Json(payload): Json<Vec<ContainerService>>,
 ) -> (StatusCode, Json<()>) {
-    let repo_ref = params.repo_id.clone();
-    println!("save_container {:?}", repo_ref);
+    println!("save_container {:?}", params.repo_id);
 
-    match app.semantic {
-        Some(ref semantic) => {
-            payload.iter().for_each(|container| {
-                let _ = &container.resources.iter().for_each(|resource| {
-                    tokio::task::block_in_place(|| {
-                        Handle::current().block_on(async {
-                            let display_text = resource.display();
-                            println!("container resource: {:?}", display_text);
-                            let _ = semantic.insert_points_for_buffer(
-                                params.repo_id.as_str(),
-                                repo_ref.as_str(),
-                                params.path.as_str(),
-                                display_text.as_str(),
-                                params.language.as_str(),
-                                PayloadType::HttpApi,
-                                display_text.as_str(),
-                            ).await;
-                        });
-                    });
-                });
-            });
-        }
-        None => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(()));
+    if index_container_services(&app, system_id, &params, &payload).await {
+        (StatusCode::CREATED, Json(()))
+    } else {
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(()))
+    }
+}
+
+/// Keep the service relations for the diagrams, and index the http apis of the services for
+/// semantic search. Returns false if semantic search is not available or a resource could not be
+/// indexed.
+pub(crate) async fn index_container_services(
+    app: &Application,
+    system_id: u32,
+    params: &ArchGuardParams,
+    payload: &[ContainerService],
+) -> bool {
+    app.graph.save_services(&system_id.to_string(), &params.repo_id, payload);
+
+    let Some(ref semantic) = app.semantic else {
+        return false;
+    };
+
+    let mut indexed = true;
+    for container in payload {
+        for resource in &container.resources {
+            let display_text = resource.display();
+            println!("container resource: {:?}", display_text);
+            if let Err(err) = semantic.insert_points_for_buffer(
+                params.repo_id.as_str(),
+                params.repo_id.as_str(),
+                params.path.as_str(),
+                display_text.as_str(),
+                params.language.as_str(),
+                PayloadType::HttpApi,
+                display_text.as_str(),
+            ).await {
+                warn!(repo = %params.repo_id, "Failed to index the resource `{}`: {:#}", display_text, err);
+                indexed = false;
+            }
         }
     }
 
-    (StatusCode::CREATED, Json(()))
+    indexed
 }
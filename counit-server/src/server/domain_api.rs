@@ -1,20 +1,119 @@
-use axum::{Extension, Json, Router};
+use axum::{Extension, extract::{Path, Query}, Json, response::IntoResponse, Router};
 use axum::http::StatusCode;
+use serde::Deserialize;
 
 use crate::application::Application;
 use crate::domain::domain_record::DomainRecord;
+use crate::domain::domain_transpiler::GlossaryError;
+use crate::server::{Error, ErrorKind};
 
 pub(crate) fn router() -> Router {
     use axum::routing::*;
 
     Router::new()
-        .route("/", get(list))
+        .route("/", get(list).post(create_record))
+        .route("/search", get(search))
+        .route("/reload", post(reload))
+        .route("/report", get(report))
+        .route("/:native", get(get_record).put(update_record).delete(delete_record))
 }
 
 
 pub async fn list(
     Extension(app): Extension<Application>,
 ) -> (StatusCode, Json<Vec<DomainRecord>>) {
-    let records = app.transpiler.domain_records.clone();
+    let records = app.transpiler.records();
     return (StatusCode::OK, Json(records));
 }
+
+#[derive(Debug, Deserialize)]
+pub struct SearchArgs {
+    pub q: String,
+}
+
+/// The records with the text in their native word, English name, abbreviation or description.
+pub(crate) async fn search(
+    Query(args): Query<SearchArgs>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    Json(app.transpiler.search(&args.q))
+}
+
+/// The edits write the glossary files, off the async runtime like [reload].
+pub(crate) async fn create_record(
+    Extension(app): Extension<Application>,
+    Json(record): Json<DomainRecord>,
+) -> impl IntoResponse {
+    let transpiler = app.transpiler.clone();
+    let record = tokio::task::spawn_blocking(move || transpiler.create(record))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok::<_, Error>((StatusCode::CREATED, Json(record)))
+}
+
+pub(crate) async fn get_record(
+    Path(native): Path<String>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    match app.transpiler.get(&native) {
+        Some(record) => Ok(Json(record)),
+        None => Err(Error::from(GlossaryError::NotFound(native))),
+    }
+}
+
+/// Replace the record of the native word, the body may rename it.
+pub(crate) async fn update_record(
+    Path(native): Path<String>,
+    Extension(app): Extension<Application>,
+    Json(record): Json<DomainRecord>,
+) -> impl IntoResponse {
+    let transpiler = app.transpiler.clone();
+    let record = tokio::task::spawn_blocking(move || transpiler.update(&native, record))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok::<_, Error>(Json(record))
+}
+
+pub(crate) async fn delete_record(
+    Path(native): Path<String>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let transpiler = app.transpiler.clone();
+    tokio::task::spawn_blocking(move || transpiler.delete(&native))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok::<_, Error>(StatusCode::NO_CONTENT)
+}
+
+/// Load the files of the domain language directory again, like when they are changed with
+/// `domain_language_watch`, returns what was skipped.
+pub(crate) async fn reload(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let transpiler = app.transpiler.clone();
+    let report = tokio::task::spawn_blocking(move || transpiler.reload())
+        .await
+        .map_err(anyhow::Error::from)?
+        .map_err(|err| Error::new(ErrorKind::Configuration, format!("{:#}", err)))?;
+
+    Ok::<_, Error>(Json(report))
+}
+
+/// The files of the glossary as they were last loaded, with the records which were skipped.
+pub(crate) async fn report(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    Json(app.transpiler.report())
+}
+
+impl From<GlossaryError> for Error {
+    fn from(error: GlossaryError) -> Self {
+        match error {
+            GlossaryError::Invalid(_) => Error::user(error),
+            GlossaryError::Duplicate(_) => Error::user(error).with_status(StatusCode::CONFLICT),
+            GlossaryError::NotFound(_) => Error::new(ErrorKind::NotFound, error.to_string()),
+            GlossaryError::SkippedRecords(_) => Error::user(error).with_status(StatusCode::CONFLICT),
+            GlossaryError::Persistence { .. } => Error::internal(error),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use axum::{Extension, extract::{Path, Query}, Json, response::IntoResponse, Router};
+use axum::http::StatusCode;
+use serde::Deserialize;
+
+use crate::application::Application;
+use crate::dsl::example_library::{ExampleError, ExampleRecord};
+use crate::dsl::query_description::QAExample;
+use crate::server::{Error, ErrorKind, json};
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/", get(list_examples).post(create_example))
+        .route("/:id", get(get_example).put(update_example).delete(delete_example))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExampleListArgs {
+    /// only the examples of this domain
+    pub domain: Option<String>,
+}
+
+/// The few-shot examples of the explain prompt.
+pub(crate) async fn list_examples(
+    Query(args): Query<ExampleListArgs>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    json(app.examples.list(args.domain.as_deref()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExampleArgs {
+    /// the name of the file of the example, generated if not given
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub example: QAExample,
+}
+
+pub(crate) async fn create_example(
+    Extension(app): Extension<Application>,
+    Json(args): Json<CreateExampleArgs>,
+) -> impl IntoResponse {
+    let id = args.id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    if app.examples.get(&id).is_some() {
+        return Err(Error::user(format!("example {} already exists", id)));
+    }
+
+    let record = app.examples.save(&id, args.example)?;
+    Ok((StatusCode::CREATED, json(record)))
+}
+
+pub(crate) async fn get_example(
+    Path(id): Path<String>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    match app.examples.get(&id) {
+        Some(record) => Ok(json(record)),
+        None => Err(Error::new(ErrorKind::NotFound, format!("example {} not found", id))),
+    }
+}
+
+/// Create or replace the example.
+pub(crate) async fn update_example(
+    Path(id): Path<String>,
+    Extension(app): Extension<Application>,
+    Json(example): Json<QAExample>,
+) -> impl IntoResponse {
+    let record = app.examples.save(&id, example)?;
+    Ok::<_, Error>(json(record))
+}
+
+pub(crate) async fn delete_example(
+    Path(id): Path<String>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    if !app.examples.delete(&id)? {
+        return Err(Error::new(ErrorKind::NotFound, format!("example {} not found", id)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+impl From<ExampleError> for Error {
+    fn from(error: ExampleError) -> Self {
+        match error {
+            ExampleError::InvalidId(_) => Error::user(error),
+            ExampleError::Persistence { .. } => Error::internal(error),
+        }
+    }
+}
+
+impl crate::server::ApiResponse for ExampleRecord {}
+
+impl crate::server::ApiResponse for Vec<ExampleRecord> {}
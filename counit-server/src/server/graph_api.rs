@@ -0,0 +1,67 @@
+use axum::{Extension, extract::Query, response::IntoResponse, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::application::Application;
+use crate::graph::call_graph::Graph;
+use crate::graph::graph_render::DiagramFormat;
+use crate::server::json;
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/services", get(services))
+        .route("/datamap", get(datamap))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphQuery {
+    /// the ArchGuard system id, all systems when empty
+    pub system: Option<String>,
+    /// start from the nodes of this repo, the whole system when empty
+    pub repo: Option<String>,
+    /// max hops from the nodes of `repo`
+    pub depth: Option<usize>,
+    #[serde(default)]
+    pub format: DiagramFormat,
+}
+
+pub(crate) async fn services(
+    Query(args): Query<GraphQuery>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let entries = app.graph.services(args.system.as_deref());
+    let graph = Graph::from_services(&entries).neighbourhood(args.repo.as_deref(), args.depth);
+
+    json(GraphResponse::new(graph, args.format))
+}
+
+pub(crate) async fn datamap(
+    Query(args): Query<GraphQuery>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let entries = app.graph.datamaps(args.system.as_deref());
+    let graph = Graph::from_datamaps(&entries).neighbourhood(args.repo.as_deref(), args.depth);
+
+    json(GraphResponse::new(graph, args.format))
+}
+
+impl crate::server::ApiResponse for GraphResponse {}
+
+#[derive(Serialize)]
+pub struct GraphResponse {
+    pub format: DiagramFormat,
+    /// the rendered diagram source
+    pub content: String,
+    pub graph: Graph,
+}
+
+impl GraphResponse {
+    fn new(graph: Graph, format: DiagramFormat) -> Self {
+        GraphResponse {
+            format,
+            content: graph.render(format),
+            graph,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, RwLock};
+
+use axum::{Extension, extract::Query, Json, response::IntoResponse, Router};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::Stream;
+use serde::Deserialize;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use uuid::Uuid;
+
+use crate::application::Application;
+use crate::mcp::McpServer;
+use crate::mcp::protocol::JsonRpcResponse;
+use crate::server::{Error, ErrorKind};
+
+/// Where the clients of the SSE transport post their messages, under `/api/mcp`.
+const MESSAGE_ENDPOINT: &str = "/api/mcp/message";
+
+/// The SSE streams of the connected clients, by session id.
+#[derive(Clone, Default)]
+struct McpSessions(Arc<RwLock<HashMap<Uuid, UnboundedSender<JsonRpcResponse>>>>);
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/", post(message))
+        .route("/sse", get(sse))
+        .route("/message", post(session_message))
+        .layer(Extension(McpSessions::default()))
+}
+
+/// Handle a JSON-RPC message and return its response in the body, for the clients which don't
+/// keep an SSE stream open.
+async fn message(
+    Extension(app): Extension<Application>,
+    body: String,
+) -> impl IntoResponse {
+    match McpServer::new(app).handle_message(&body).await {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// The SSE transport: the first event is the `endpoint` to post the messages to, then the
+/// responses are sent as `message` events.
+async fn sse(
+    Extension(sessions): Extension<McpSessions>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let session_id = Uuid::new_v4();
+    let (response_tx, response_rx) = tokio::sync::mpsc::unbounded_channel();
+    sessions.0.write().unwrap().insert(session_id, response_tx);
+
+    let endpoint = Event::default()
+        .event("endpoint")
+        .data(format!("{}?session_id={}", MESSAGE_ENDPOINT, session_id));
+
+    let session = Session { id: session_id, sessions, responses: response_rx };
+    let responses = futures::stream::unfold(session, |mut session| async move {
+        let response = session.responses.recv().await?;
+        let event = Event::default()
+            .event("message")
+            .json_data(response)
+            .unwrap_or_else(|err| Event::default().event("error").data(err.to_string()));
+        Some((Ok(event), session))
+    });
+
+    let events = futures::StreamExt::chain(futures::stream::once(async { Ok(endpoint) }), responses);
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+/// The receiver of the responses of a session, which ends the session when the stream is dropped.
+struct Session {
+    id: Uuid,
+    sessions: McpSessions,
+    responses: UnboundedReceiver<JsonRpcResponse>,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.sessions.0.write().unwrap().remove(&self.id);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionArgs {
+    pub session_id: Uuid,
+}
+
+async fn session_message(
+    Query(args): Query<SessionArgs>,
+    Extension(app): Extension<Application>,
+    Extension(sessions): Extension<McpSessions>,
+    body: String,
+) -> impl IntoResponse {
+    let Some(response_tx) = sessions.0.read().unwrap().get(&args.session_id).cloned() else {
+        return Err(Error::new(ErrorKind::NotFound, format!("mcp session {} not found", args.session_id)));
+    };
+
+    // the response is sent on the SSE stream, tools can take a while so don't hold the request
+    tokio::spawn(async move {
+        if let Some(response) = McpServer::new(app).handle_message(&body).await {
+            let _ = response_tx.send(response);
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
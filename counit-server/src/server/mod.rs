@@ -5,7 +5,14 @@ use axum::response::IntoResponse;
 
 pub mod domain_api;
 pub mod archguard_api;
+pub mod analyser_api;
 pub mod semantic_api;
+pub mod path_api;
+pub mod example_api;
+pub mod graph_api;
+pub mod mcp_api;
+pub mod model_api;
+pub mod openai_api;
 
 pub mod agent_api;
 
@@ -0,0 +1,37 @@
+use axum::{Extension, Json, response::IntoResponse, Router};
+use axum::http::StatusCode;
+
+use crate::application::Application;
+use crate::server::{Error, ErrorKind};
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/", get(current))
+        .route("/reload", post(reload))
+}
+
+/// The settings of the chat model in use.
+pub(crate) async fn current(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    Json(app.model.current().summary())
+}
+
+/// Read the `llm_*` settings of `public/config.json` again and replace the chat model, the
+/// previous one is kept if the configuration is invalid. A server started with the development
+/// defaults has no file to reload.
+pub(crate) async fn reload(
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let Some(config_file) = app.config_file.as_deref() else {
+        return Err(Error::new(ErrorKind::Configuration, "the server was started without public/config.json, there is nothing to reload")
+            .with_status(StatusCode::CONFLICT));
+    };
+
+    let summary = app.reload_model(config_file)
+        .map_err(|err| Error::new(ErrorKind::Configuration, format!("{:#}", err)))?;
+
+    Ok::<_, Error>(Json(summary))
+}
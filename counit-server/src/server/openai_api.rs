@@ -0,0 +1,228 @@
+use std::convert::Infallible;
+
+use axum::{Extension, Json, response::{IntoResponse, Response}, Router};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::chat::{ChatContext, retrieval_chat};
+use crate::application::Application;
+use crate::llm::ChatMessage;
+use crate::repository::payload::PayloadType;
+use crate::server::{Error, ErrorKind};
+
+pub(crate) fn router() -> Router {
+    use axum::routing::*;
+
+    Router::new()
+        .route("/chat/completions", post(chat_completions))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCompletionRequest {
+    /// ignored, the configured `llm_model` answers
+    pub model: Option<String>,
+    pub messages: Vec<RequestMessage>,
+    #[serde(default)]
+    pub stream: bool,
+    /// only retrieve the snippets of these repos, an extension of the OpenAI api
+    #[serde(default)]
+    pub repos: Vec<String>,
+    /// the kind of snippets to retrieve, code by default, an extension of the OpenAI api
+    pub r#type: Option<PayloadType>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RequestMessage {
+    pub role: String,
+    /// null for the tool calls of the assistant
+    #[serde(default)]
+    pub content: Option<MessageContent>,
+}
+
+/// The content of a message, a text or the parts of a multimodal message.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentPart {
+    pub r#type: String,
+    pub text: Option<String>,
+}
+
+impl RequestMessage {
+    /// The text of the message, the parts which are not text are left out.
+    fn text(&self) -> String {
+        match &self.content {
+            Some(MessageContent::Text(text)) => text.clone(),
+            Some(MessageContent::Parts(parts)) => parts.iter()
+                .filter(|it| it.r#type == "text")
+                .filter_map(|it| it.text.as_deref())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChatCompletion {
+    pub id: String,
+    pub object: &'static str,
+    pub created: i64,
+    pub model: String,
+    pub choices: Vec<Choice>,
+    pub usage: Usage,
+    /// the question searched and the snippets given to the model
+    pub metadata: ChatContext,
+}
+
+#[derive(Serialize)]
+pub struct Choice {
+    pub index: usize,
+    pub message: ChatMessage,
+    pub finish_reason: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+}
+
+#[derive(Serialize)]
+pub struct ChatCompletionChunk {
+    pub id: String,
+    pub object: &'static str,
+    pub created: i64,
+    pub model: String,
+    pub choices: Vec<ChunkChoice>,
+    /// only in the first chunk
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ChatContext>,
+}
+
+#[derive(Serialize)]
+pub struct ChunkChoice {
+    pub index: usize,
+    pub delta: Delta,
+    pub finish_reason: Option<&'static str>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Delta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// OpenAI-compatible chat completions, so that the existing chat clients can use CoUnit: the
+/// last question is translated by the glossary and searched, the snippets found are given to
+/// the configured model, and returned in `metadata.citations`. With `stream`, the completion is
+/// sent as `chat.completion.chunk` server-sent events, ended by `[DONE]`, or by an error event if
+/// the model fails.
+pub(crate) async fn chat_completions(
+    Extension(app): Extension<Application>,
+    Json(request): Json<ChatCompletionRequest>,
+) -> Response {
+    match chat_completion(app, request).await {
+        Ok(response) => response,
+        Err(err) => {
+            let error_type = if err.status.is_client_error() { "invalid_request_error" } else { "server_error" };
+            (err.status, Json(error_body(err.message(), error_type))).into_response()
+        }
+    }
+}
+
+/// An error in the shape of the OpenAI api, so that its clients can show it.
+fn error_body(message: &str, error_type: &str) -> serde_json::Value {
+    serde_json::json!({ "error": { "message": message, "type": error_type, "param": null, "code": null } })
+}
+
+async fn chat_completion(app: Application, request: ChatCompletionRequest) -> Result<Response, Error> {
+    let current = app.model.current();
+    let (Some(llm), Some(_)) = (current.client.clone(), app.semantic.as_ref()) else {
+        return Err(Error::new(ErrorKind::Configuration, "the chat needs `llm_base_url` and `qdrant_url`"));
+    };
+
+    let messages: Vec<ChatMessage> = request.messages.iter()
+        .map(|it| ChatMessage { role: it.role.clone(), content: it.text() })
+        .collect();
+    if !messages.iter().any(|it| it.role == "user") {
+        return Err(Error::user("the chat has no message of the user"));
+    }
+
+    let payload_type = request.r#type.unwrap_or_default();
+    let (prompt, context) = retrieval_chat(&app, &current, &messages, &request.repos, payload_type).await?;
+
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+    let created = chrono::Utc::now().timestamp();
+    let model = current.name.clone();
+
+    if !request.stream {
+        let content = llm.chat(&prompt).await?;
+        let prompt_tokens = prompt.iter().map(|it| app.tokens.count(&it.content)).sum();
+        let completion_tokens = app.tokens.count(&content);
+
+        return Ok(Json(ChatCompletion {
+            id,
+            object: "chat.completion",
+            created,
+            model,
+            choices: vec![Choice { index: 0, message: ChatMessage::assistant(&content), finish_reason: "stop" }],
+            usage: Usage { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens },
+            metadata: context,
+        }).into_response());
+    }
+
+    let deltas = llm.chat_stream(&prompt).await?;
+    let chunk = move |delta: Delta, finish_reason: Option<&'static str>, metadata: Option<ChatContext>| {
+        let chunk = ChatCompletionChunk {
+            id: id.clone(),
+            object: "chat.completion.chunk",
+            created,
+            model: model.clone(),
+            choices: vec![ChunkChoice { index: 0, delta, finish_reason }],
+            metadata,
+        };
+        Event::default().json_data(chunk)
+            .unwrap_or_else(|err| Event::default().event("error").data(err.to_string()))
+    };
+
+    let start = chunk(Delta { role: Some("assistant"), content: Some(String::new()) }, None, Some(context));
+    let chunk_delta = chunk.clone();
+    let contents = deltas.map(move |delta| match delta {
+        Ok(content) => Ok(chunk_delta(Delta { content: Some(content), ..Default::default() }, None, None)),
+        Err(err) => Err(Event::default()
+            .json_data(error_body(&err.to_string(), "server_error"))
+            .unwrap_or_else(|err| Event::default().event("error").data(err.to_string()))),
+    });
+    let stop = chunk(Delta::default(), Some("stop"), None);
+
+    // the stream ends with the error of the model, without the stop chunk and `[DONE]`, so that
+    // the clients don't take the partial completion as complete
+    let events = futures::stream::once(async { Ok(start) })
+        .chain(contents)
+        .chain(futures::stream::once(async { Ok(stop) }))
+        .chain(futures::stream::once(async { Ok(Event::default().data("[DONE]")) }))
+        .scan(false, |failed, event| {
+            let event = match event {
+                _ if *failed => None,
+                Ok(event) => Some(event),
+                Err(event) => {
+                    *failed = true;
+                    Some(event)
+                }
+            };
+            futures::future::ready(event)
+        })
+        .map(Ok::<_, Infallible>);
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+}
@@ -0,0 +1,34 @@
+use axum::{Extension, extract::Query, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+
+use crate::application::Application;
+use crate::repository::path_index::PathMatch;
+use crate::server::json;
+
+const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct PathQuery {
+    pub q: String,
+    /// only search the paths of this repo
+    pub repo: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// Fuzzy search of the paths of the ingested code, like `/api/paths?q=paymentsvc`.
+pub(crate) async fn paths(
+    Query(args): Query<PathQuery>,
+    Extension(app): Extension<Application>,
+) -> impl IntoResponse {
+    let repos: Vec<String> = args.repo.into_iter().collect();
+    let data = app.paths.search(&args.q, &repos, args.limit.unwrap_or(DEFAULT_LIMIT));
+
+    json(PathResponse { data })
+}
+
+impl crate::server::ApiResponse for PathResponse {}
+
+#[derive(Serialize)]
+pub struct PathResponse {
+    pub data: Vec<PathMatch>,
+}
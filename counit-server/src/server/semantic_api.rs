@@ -1,21 +1,26 @@
 use axum::{
-    body::HttpBody, Extension, extract::Query, response::IntoResponse,
+    body::HttpBody, Extension, extract::Query, response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
 
 use crate::application::Application;
+use crate::llm::hypothetical_document;
 use crate::model::dto::query::SimpleQuery;
 use crate::repository::{
-    payload::CodePayload, semantic_query::SemanticQuery,
+    payload::CodePayload, semantic_query::{SearchVector, SemanticQuery},
 };
 use crate::repository::payload::PayloadType;
 use crate::repository::semantic::Embedding;
-use crate::server::{Error, json};
+use crate::server::{Error, ErrorKind, json};
 
 pub(crate) async fn query(
     Query(args): Query<ApiQuery>,
     Extension(app): Extension<Application>,
-) -> impl IntoResponse {
+) -> Response {
+    if args.hyde {
+        return hyde_query(args, app).await.into_response();
+    }
+
     let q = SemanticQuery::from_str(args.q, args.r#type);
 
     let result = app.semantic
@@ -30,7 +35,39 @@ pub(crate) async fn query(
         Err(err) => {
             Err(Error::from(err))
         }
-    }
+    }.into_response()
+}
+
+/// HyDE (Hypothetical Document Embeddings) search: the model writes a REST api snippet which could
+/// answer the query, and the snippets are searched with the vectors of both the query and the
+/// hypothetical document.
+async fn hyde_query(args: ApiQuery, app: Application) -> Result<impl IntoResponse, Error> {
+    let (Some(llm), Some(semantic)) = (app.model.client(), app.semantic.as_ref()) else {
+        return Err(Error::new(ErrorKind::Configuration, "HyDE search needs `llm_base_url` and `qdrant_url`"));
+    };
+
+    let document = hypothetical_document(llm.as_ref(), &app.templates, &args.q).await?;
+    let query = SemanticQuery::from_str(args.q, args.r#type.clone());
+    let document_query = SemanticQuery::from_str(document.clone(), args.r#type);
+
+    let results = semantic
+        .batch_search_with_sources(&[&query, &document_query], 10, 0, 0.0, true)
+        .await?;
+
+    let data = search_hits(results, &[SearchVector::Query, SearchVector::HypotheticalDocument]);
+
+    Ok(json(HydeQueryResponse { hypothetical_document: document, data }))
+}
+
+/// The hits of [crate::repository::semantic::Semantic::batch_search_with_sources], `vectors`
+/// are the texts of the queries in the order of the search.
+pub(crate) fn search_hits(results: Vec<(CodePayload, Vec<usize>)>, vectors: &[SearchVector]) -> Vec<SearchHit> {
+    results.into_iter()
+        .map(|(payload, sources)| SearchHit {
+            payload,
+            sources: sources.into_iter().filter_map(|it| vectors.get(it).copied()).collect(),
+        })
+        .collect()
 }
 
 pub(crate) async fn embedding(
@@ -62,6 +99,9 @@ pub struct EmbeddingResponse {
 pub struct ApiQuery {
     pub q: String,
     pub r#type: PayloadType,
+    /// search with a hypothetical document written by the model too
+    #[serde(default)]
+    pub hyde: bool,
 }
 
 impl crate::server::ApiResponse for QueryResponse {}
@@ -70,3 +110,19 @@ impl crate::server::ApiResponse for QueryResponse {}
 pub struct QueryResponse {
     pub data: Vec<CodePayload>,
 }
+
+/// A snippet of a multi-vector search, with the texts whose vectors found it.
+#[derive(Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub payload: CodePayload,
+    pub sources: Vec<SearchVector>,
+}
+
+impl crate::server::ApiResponse for HydeQueryResponse {}
+
+#[derive(Serialize)]
+pub struct HydeQueryResponse {
+    pub hypothetical_document: String,
+    pub data: Vec<SearchHit>,
+}